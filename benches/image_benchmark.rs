@@ -6,6 +6,10 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("generate 10,000 x 10,000", |b| {
         b.iter(|| generate(black_box(10_000), black_box(10_000)))
     });
+    #[cfg(feature = "parallel")]
+    c.bench_function("parallel generate 10,000 x 10,000", |b| {
+        b.iter(|| ulam::parallel::generate(black_box(10_000), black_box(10_000)))
+    });
     // group.bench_function("generate 10,000 x 10,000 old", |b| {
     //     b.iter(|| generate_old(black_box(10_000), black_box(10_000)))
     // });
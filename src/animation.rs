@@ -0,0 +1,387 @@
+/// Animations of the spiral growing value by value or ring by ring, or zooming out, as GIF or APNG.
+use std::{collections::HashMap, error::Error, io::Write};
+
+use crate::{
+    calc_coord::isqrt,
+    mask::Mask,
+    palette::{over, Cell, ColourMapper},
+    ulamspiral_img::{canvas, fill},
+    value_of_coord_u64,
+    viewport::Viewport,
+    Coord,
+};
+
+/// How the spiral appears over the frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reveal {
+    /// The same number of new values every frame, in spiral order.
+    Values,
+    /// Whole rings at a time, the same number every frame.
+    Rings,
+    /// Everything from the start, zooming out from `from_cells` across the middle to the whole viewport.
+    Zoom { from_cells: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Frames with more than 256 colours are quantized to 256.
+    Gif,
+    Apng,
+}
+
+/// An animation of a viewport, the last frame is the whole viewport drawn as `render_colour` draws it.
+/// # Examples
+/// ```no_run
+/// use std::{fs::File, io::BufWriter};
+/// use ulam::{animation::{Animation, Format, Reveal}, palette::ModSix, predicate::Primes, viewport::Viewport};
+/// let file = BufWriter::new(File::create("growth.gif").unwrap());
+/// Animation::new(Reveal::Rings)
+///     .with_frames(100)
+///     .with_fps(20)
+///     .write(file, Format::Gif, &Viewport::new(201, 201).with_scale(2), &Primes::new(), &ModSix)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Animation {
+    pub reveal: Reveal,
+    pub frames: u32,
+    pub fps: u16,
+    /// Behind cells not yet revealed and cells the mapper leaves transparent.
+    pub background: [u8; 3],
+}
+
+impl Animation {
+    /// 60 frames at 12 a second on black.
+    pub fn new(reveal: Reveal) -> Animation {
+        Animation {
+            reveal,
+            frames: 60,
+            fps: 12,
+            background: [0, 0, 0],
+        }
+    }
+
+    pub fn with_frames(self, frames: u32) -> Animation {
+        Animation { frames, ..self }
+    }
+
+    pub fn with_fps(self, fps: u16) -> Animation {
+        Animation { fps, ..self }
+    }
+
+    pub fn with_background(self, background: [u8; 3]) -> Animation {
+        Animation { background, ..self }
+    }
+
+    /// Draw every frame, calling `frame` with each in turn.
+    pub fn for_each_frame<P, M, F>(
+        &self,
+        viewport: &Viewport,
+        predicate: &P,
+        mapper: &M,
+        mut frame: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        P: crate::predicate::NumberPredicate + ?Sized,
+        M: ColourMapper + ?Sized,
+        F: FnMut(&image::RgbImage) -> Result<(), Box<dyn Error>>,
+    {
+        if viewport.downsample > 1 {
+            return Err("animations are not downsampled".into());
+        }
+        if self.frames == 0 || self.fps == 0 {
+            return Err("an animation needs at least one frame and one frame a second".into());
+        }
+        let mask = Mask::from_viewport(viewport, predicate);
+        let colour = |col: u32, row: u32| {
+            let coord = viewport.coord_at(col, row);
+            let cell = Cell::new(coord, value_of_coord_u64(&coord), mask.get(col, row));
+            image::Rgb(over(mapper.colour(&cell), self.background))
+        };
+        let mut img: image::RgbImage = canvas(viewport)?;
+        for pixel in img.pixels_mut() {
+            *pixel = image::Rgb(self.background);
+        }
+        let frames = u64::from(self.frames);
+        let values = viewport.max_value() + 1;
+        // the last ring with any value in view.
+        let rings = isqrt(viewport.max_value()).div_ceil(2) + 1;
+        // the runs of the spiral across the viewport with the first value of each.
+        let arms: Vec<(u64, Coord, (i32, i32), u64)> = viewport
+            .arms(0..viewport.height)
+            .into_iter()
+            .map(|(start, step, len)| (value_of_coord_u64(&start), start, step, len))
+            .collect();
+        let mut next = 0;
+        for i in 1..=frames {
+            let upto = match self.reveal {
+                Reveal::Values => (i * values).div_ceil(frames),
+                Reveal::Rings => {
+                    let ring = (i * rings).div_ceil(frames) - 1;
+                    (2 * ring + 1).pow(2).min(values)
+                }
+                Reveal::Zoom { from_cells } => {
+                    let t = if frames == 1 {
+                        1.0
+                    } else {
+                        (i - 1) as f64 / (frames - 1) as f64
+                    };
+                    let start = f64::from(from_cells.max(1)) / f64::from(viewport.width.max(1));
+                    zoom(&mut img, viewport, start.min(1.0).powf(1.0 - t), &colour);
+                    frame(&img)?;
+                    continue;
+                }
+            };
+            // only the values in view, a run at a time.
+            for (first, start, step, len) in &arms {
+                for value in next.max(*first)..upto.min(first + len) {
+                    let along = (value - first) as i32;
+                    let c = Coord::new(start.x + step.0 * along, start.y + step.1 * along);
+                    if let Some((col, row)) = viewport.cell_of(&c) {
+                        fill(&mut img, viewport.scale, col, row, colour(col, row));
+                    }
+                }
+            }
+            next = upto;
+            frame(&img)?;
+        }
+        Ok(())
+    }
+
+    /// Every frame, for adding to or stitching by hand.
+    pub fn frames<P, M>(
+        &self,
+        viewport: &Viewport,
+        predicate: &P,
+        mapper: &M,
+    ) -> Result<Vec<image::RgbImage>, Box<dyn Error>>
+    where
+        P: crate::predicate::NumberPredicate + ?Sized,
+        M: ColourMapper + ?Sized,
+    {
+        let mut frames = Vec::new();
+        self.for_each_frame(viewport, predicate, mapper, |img| {
+            frames.push(img.clone());
+            Ok(())
+        })?;
+        Ok(frames)
+    }
+
+    /// Write the animation to `writer`, looping forever.
+    pub fn write<W, P, M>(
+        &self,
+        writer: W,
+        format: Format,
+        viewport: &Viewport,
+        predicate: &P,
+        mapper: &M,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        W: Write,
+        P: crate::predicate::NumberPredicate + ?Sized,
+        M: ColourMapper + ?Sized,
+    {
+        let (width, height) = (viewport.pixel_width()?, viewport.pixel_height()?);
+        match format {
+            Format::Gif => {
+                let (width, height) = (
+                    u16::try_from(width).map_err(|_| "too wide for a GIF")?,
+                    u16::try_from(height).map_err(|_| "too tall for a GIF")?,
+                );
+                let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                // GIF delays are in hundredths of a second.
+                let delay = ((100 + self.fps / 2) / self.fps).max(1);
+                self.for_each_frame(viewport, predicate, mapper, |img| {
+                    // exact colours when they fit in one palette, quantized when they do not.
+                    let mut frame = match indexed(img) {
+                        Some((pixels, palette)) => {
+                            gif::Frame::from_palette_pixels(width, height, pixels, palette, None)
+                        }
+                        None => gif::Frame::from_rgb_speed(width, height, img.as_raw(), 10),
+                    };
+                    frame.delay = delay;
+                    encoder.write_frame(&frame)?;
+                    Ok(())
+                })
+            }
+            Format::Apng => {
+                let mut encoder = png::Encoder::new(writer, width, height);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_compression(png::Compression::Fast);
+                encoder.set_animated(self.frames, 0)?;
+                encoder.set_frame_delay(1, self.fps)?;
+                let mut png = encoder.write_header()?;
+                self.for_each_frame(viewport, predicate, mapper, |img| {
+                    png.write_image_data(img.as_raw())?;
+                    Ok(())
+                })?;
+                png.finish()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+// Draw the middle `fraction` of the viewport stretched over the whole image.
+fn zoom<F: Fn(u32, u32) -> image::Rgb<u8>>(
+    img: &mut image::RgbImage,
+    viewport: &Viewport,
+    fraction: f64,
+    colour: &F,
+) {
+    let scale = f64::from(viewport.scale);
+    let (width, height) = (f64::from(img.width()), f64::from(img.height()));
+    let cell = |pixel: u32, pixels: f64, cells: u32| {
+        let at =
+            f64::from(cells) / 2.0 + (f64::from(pixel) + 0.5 - pixels / 2.0) / scale * fraction;
+        (at.max(0.0) as u32).min(cells - 1)
+    };
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        *pixel = colour(
+            cell(x, width, viewport.width),
+            cell(y, height, viewport.height),
+        );
+    }
+}
+
+// The frame as palette indices and its palette, `None` past 256 colours.
+fn indexed(img: &image::RgbImage) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut index = HashMap::new();
+    let mut palette = Vec::new();
+    let mut pixels = Vec::with_capacity(img.as_raw().len() / 3);
+    for pixel in img.pixels() {
+        let next = index.len();
+        let i = *index.entry(pixel.0).or_insert(next);
+        if i == next {
+            if next == 256 {
+                return None;
+            }
+            palette.extend_from_slice(&pixel.0);
+        }
+        pixels.push(i as u8);
+    }
+    Some((pixels, palette))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        palette::{ModSix, ValueGradient},
+        predicate::Primes,
+        ulamspiral_img::render_colour,
+    };
+    #[test]
+    fn check_reveals() {
+        let viewport = Viewport::new(9, 7).with_scale(2);
+        let primes = Primes::new();
+        let last = render_colour(&viewport, &primes, &ModSix).unwrap();
+        for reveal in [
+            Reveal::Values,
+            Reveal::Rings,
+            Reveal::Zoom { from_cells: 3 },
+        ] {
+            let frames = Animation::new(reveal)
+                .with_frames(5)
+                .frames(&viewport, &primes, &ModSix)
+                .unwrap();
+            assert_eq!(frames.len(), 5);
+            assert_eq!(frames[4], last, "{reveal:?}");
+            assert_ne!(frames[0], last, "{reveal:?}");
+        }
+        // a 9 by 7 view reaches ring 4, one ring a frame
+        let grey = |_: &Cell| [200, 200, 200, 255];
+        let rings = Animation::new(Reveal::Rings)
+            .with_frames(5)
+            .with_background([1, 2, 3])
+            .frames(&viewport, &primes, &grey)
+            .unwrap();
+        let unrevealed = |img: &image::RgbImage| img.pixels().filter(|p| p.0 == [1, 2, 3]).count();
+        // only 0 is in the first frame, then the 8 cells around it
+        assert_eq!(unrevealed(&rings[0]), (9 * 7 - 1) * 4);
+        assert_eq!(unrevealed(&rings[1]), (9 * 7 - 9) * 4);
+        assert_eq!(unrevealed(&rings[4]), 0);
+        // far out only the values in view are walked
+        let far = Viewport::centered_on(crate::Coord::new(100_000, 0), 9, 7);
+        let frames = Animation::new(Reveal::Values)
+            .with_frames(3)
+            .frames(&far, &primes, &ModSix)
+            .unwrap();
+        assert_eq!(frames[2], render_colour(&far, &primes, &ModSix).unwrap());
+        assert!(Animation::new(Reveal::Values)
+            .with_frames(0)
+            .frames(&viewport, &primes, &ModSix)
+            .is_err());
+    }
+    #[test]
+    fn check_gif() {
+        let viewport = Viewport::new(21, 21);
+        let mut gif = Vec::new();
+        Animation::new(Reveal::Values)
+            .with_frames(4)
+            .write(&mut gif, Format::Gif, &viewport, &Primes::new(), &ModSix)
+            .unwrap();
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = decoder.read_info(gif.as_slice()).unwrap();
+        let mut frames = 0;
+        let last = render_colour(&viewport, &Primes::new(), &ModSix).unwrap();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames += 1;
+            assert_eq!(frame.delay, 8);
+            if frames == 4 {
+                let rgb: Vec<u8> = frame
+                    .buffer
+                    .chunks(4)
+                    .flat_map(|p| &p[..3])
+                    .copied()
+                    .collect();
+                assert_eq!(&rgb, last.as_raw());
+            }
+        }
+        assert_eq!(frames, 4);
+        // a gradient has too many colours for one palette, so it is quantized
+        let viewport = Viewport::new(41, 41);
+        let gradient = ValueGradient {
+            all_cells: true,
+            ..ValueGradient::new(viewport.max_value())
+        };
+        let last = render_colour(&viewport, &Primes::new(), &gradient).unwrap();
+        assert!(indexed(&last).is_none());
+        let mut gif = Vec::new();
+        Animation::new(Reveal::Values)
+            .with_frames(3)
+            .write(&mut gif, Format::Gif, &viewport, &Primes::new(), &gradient)
+            .unwrap();
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = decoder.read_info(gif.as_slice()).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push(frame.buffer.to_vec());
+        }
+        assert_eq!(frames.len(), 3);
+        let worst = frames[2]
+            .chunks(4)
+            .zip(last.pixels())
+            .flat_map(|(got, want)| got[..3].iter().zip(want.0).map(|(g, w)| g.abs_diff(w)))
+            .max();
+        assert!(worst <= Some(32), "{worst:?}");
+    }
+    #[test]
+    fn check_apng() {
+        let viewport = Viewport::new(21, 21);
+        let mut apng = Vec::new();
+        Animation::new(Reveal::Zoom { from_cells: 5 })
+            .with_frames(3)
+            .with_fps(25)
+            .write(&mut apng, Format::Apng, &viewport, &Primes::new(), &ModSix)
+            .unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(apng));
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, 0));
+    }
+}
@@ -0,0 +1,24 @@
+/// Serve spiral tiles and lookups over HTTP.
+///
+/// ulam-server [address] [cache dir] [radius]
+use std::{env, error::Error};
+
+use ulam::{server::TileServer, tiles::Tiler};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let cache_dir = args.next().unwrap_or_else(|| "tile-cache".to_string());
+    let radius = match args.next() {
+        Some(radius) => radius.parse()?,
+        None => 10_000,
+    };
+
+    let server = tiny_http::Server::http(&addr).map_err(|e| e.to_string())?;
+    let tiler = Tiler::new(radius)?;
+    println!(
+        "serving a spiral of radius {radius} on http://{addr}, zoom 0 to {}, tiles cached in {cache_dir}",
+        tiler.max_zoom
+    );
+    TileServer::new(tiler, cache_dir.into()).serve(&server)
+}
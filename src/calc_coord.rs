@@ -30,6 +30,61 @@ pub fn calc_coord(value: u32) -> Coord {
     Coord { x, y }
 }
 
+/// Same as `calc_coord` but for values past `u32`, which sit more than 32,767 cells out.
+///
+/// # Panics
+/// From (2^32 - 1)^2 on, where the ring is too far out for `i32` coordinates.
+/// # Examples
+/// ```
+/// use ulam::{calc_coord::calc_coord_u64, Coord};
+/// assert_eq!(calc_coord_u64(40_000_000_000), Coord::new(-100_000, 100_000));
+/// ```
+pub fn calc_coord_u64(value: u64) -> Coord {
+    let x: i64;
+    let y: i64;
+
+    // the root is below 2^32 and the difference at most twice it, so both fit i64.
+    let root = isqrt(value);
+    let n: i64 = root as i64;
+    let diff: i64 = (value - root * root) as i64;
+
+    if n % 2 == 1 {
+        // odd n arm
+        if diff < n {
+            x = (n + 1) / 2;
+            y = ((1 - n) / 2) + diff;
+        } else {
+            x = (3 * n + 1) / 2 - diff;
+            y = (n + 1) / 2;
+        }
+    } else {
+        // even n arm
+        if diff < n {
+            x = -n / 2;
+            y = n / 2 - diff;
+        } else {
+            x = ((-3 * n) / 2) + diff;
+            y = -n / 2;
+        }
+    }
+    Coord {
+        x: i32::try_from(x).expect("value too far out for an i32 coordinate"),
+        y: i32::try_from(y).expect("value too far out for an i32 coordinate"),
+    }
+}
+
+/// The largest integer whose square is at most n.
+pub fn isqrt(n: u64) -> u64 {
+    let mut root = (n as f64).sqrt() as u64;
+    while root.checked_mul(root).is_none_or(|sq| sq > n) {
+        root -= 1;
+    }
+    while (root + 1).checked_mul(root + 1).is_some_and(|sq| sq <= n) {
+        root += 1;
+    }
+    root
+}
+
 pub fn calc_xy(value: u32) -> (i32, i32) {
     let x: i32;
     let y: i32;
@@ -61,7 +116,7 @@ pub fn calc_xy(value: u32) -> (i32, i32) {
 
 #[cfg(test)]
 mod tests {
-    use super::{calc_coord, Coord};
+    use super::{calc_coord, calc_coord_u64, isqrt, Coord};
 
     #[test]
     fn check_small_0() {
@@ -239,6 +294,26 @@ mod tests {
         assert_eq!(result, c);
     }
     #[test]
+    fn check_wide() {
+        for v in (0..100_000).step_by(7) {
+            assert_eq!(calc_coord_u64(v as u64), calc_coord(v));
+        }
+        let result = calc_coord_u64(4 * 40_000 * 40_000 + 4 * 40_000);
+        let c = Coord::new(40_000, -40_000);
+        assert_eq!(result, c);
+        // the last value of the last ring that fits in i32
+        let last = calc_coord_u64(4_294_967_295 * 4_294_967_295 - 1);
+        assert_eq!(last, Coord::new(i32::MAX, -i32::MAX));
+        assert!(std::panic::catch_unwind(|| calc_coord_u64(u64::MAX)).is_err());
+    }
+    #[test]
+    fn check_isqrt() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(15), 3);
+        assert_eq!(isqrt(16), 4);
+        assert_eq!(isqrt(u64::MAX), 4_294_967_295);
+    }
+    #[test]
     fn check_deep_south() {
         let result = calc_coord(3987051);
         let c = Coord::new(41, -998);
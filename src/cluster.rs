@@ -0,0 +1,210 @@
+/// Connected components of marked cells (usually primes) in a `Mask`.
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{mask::Mask, Coord};
+
+/// Which cells count as touching.
+/// `Eight` also joins diagonal cells, which is what makes the prime diagonals show up as chains.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        match self {
+            Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+            Connectivity::Eight => &[
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ],
+        }
+    }
+}
+
+/// A single connected group of cells, `min` and `max` are the corners of its bounding box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    pub label: u32,
+    pub size: usize,
+    pub min: Coord,
+    pub max: Coord,
+}
+
+#[derive(Debug, Clone)]
+pub struct Components {
+    /// One label per mask cell in the same order as `Mask::cells`, 0 is unmarked.
+    pub labels: Vec<u32>,
+    /// Component `n` has the label `n + 1`.
+    pub components: Vec<Component>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Components {
+    /// Label the marked cells of a mask.
+    /// # Examples
+    /// ```
+    /// use ulam::{cluster::{Components, Connectivity}, mask::Mask, Coord};
+    /// let is_prime = |n: u64| n > 1 && (2..n).all(|d| n % d != 0);
+    /// let mask = Mask::from_predicate(Coord::new(-2, -2), Coord::new(2, 2), &is_prime);
+    /// let comps = Components::label(&mask, Connectivity::Eight);
+    /// // every prime around the center touches another one diagonally
+    /// assert_eq!(comps.components.len(), 1);
+    /// ```
+    pub fn label(mask: &Mask, connectivity: Connectivity) -> Components {
+        let mut labels = vec![0; mask.cells.len()];
+        let mut components = Vec::new();
+        let mut queue = VecDeque::new();
+
+        for start in 0..mask.cells.len() {
+            if !mask.cells[start] || labels[start] != 0 {
+                continue;
+            }
+            let label = components.len() as u32 + 1;
+            let first = mask.coord_at(start as u32 % mask.width, start as u32 / mask.width);
+            let mut comp = Component {
+                label,
+                size: 0,
+                min: first,
+                max: first,
+            };
+            labels[start] = label;
+            queue.push_back(start);
+
+            while let Some(i) = queue.pop_front() {
+                let col = (i as u32 % mask.width) as i32;
+                let row = (i as u32 / mask.width) as i32;
+                let c = mask.coord_at(col as u32, row as u32);
+                comp.size += 1;
+                comp.min = Coord::new(comp.min.x.min(c.x), comp.min.y.min(c.y));
+                comp.max = Coord::new(comp.max.x.max(c.x), comp.max.y.max(c.y));
+
+                for (dx, dy) in connectivity.offsets() {
+                    let (ncol, nrow) = (col + dx, row + dy);
+                    if ncol < 0
+                        || nrow < 0
+                        || ncol >= mask.width as i32
+                        || nrow >= mask.height as i32
+                    {
+                        continue;
+                    }
+                    let n = (nrow as u32 * mask.width + ncol as u32) as usize;
+                    if mask.cells[n] && labels[n] == 0 {
+                        labels[n] = label;
+                        queue.push_back(n);
+                    }
+                }
+            }
+            components.push(comp);
+        }
+
+        Components {
+            labels,
+            components,
+            width: mask.width,
+            height: mask.height,
+        }
+    }
+
+    /// Maps a component size to how many components have that size.
+    pub fn size_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut hist = BTreeMap::new();
+        for comp in &self.components {
+            *hist.entry(comp.size).or_insert(0) += 1;
+        }
+        hist
+    }
+
+    /// The `n` biggest components, biggest first.
+    pub fn largest(&self, n: usize) -> Vec<&Component> {
+        let mut comps: Vec<&Component> = self.components.iter().collect();
+        comps.sort_by(|a, b| b.size.cmp(&a.size).then(a.label.cmp(&b.label)));
+        comps.truncate(n);
+        comps
+    }
+
+    /// Draw every component in its own colour on a black background.
+    #[cfg(feature = "img")]
+    pub fn render(&self) -> image::RgbImage {
+        let mut img = image::RgbImage::new(self.width, self.height);
+        for (i, label) in self.labels.iter().enumerate() {
+            if *label == 0 {
+                continue;
+            }
+            let x = i as u32 % self.width;
+            let y = i as u32 / self.width;
+            img.put_pixel(x, y, image::Rgb(label_colour(*label)));
+        }
+        img
+    }
+}
+
+/// Spread the labels around the hue circle so neighbouring labels look different.
+#[cfg(feature = "img")]
+fn label_colour(label: u32) -> [u8; 3] {
+    let hue = (label as f64 * 0.618_033_988_75).fract() * 6.0;
+    let f = hue.fract();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, f, 0.0),
+        1 => (1.0 - f, 1.0, 0.0),
+        2 => (0.0, 1.0, f),
+        3 => (0.0, 1.0 - f, 1.0),
+        4 => (f, 0.0, 1.0),
+        _ => (1.0, 0.0, 1.0 - f),
+    };
+    // keep every colour bright enough to stand out from the background.
+    let scale = |c: f64| (64.0 + c * 191.0) as u8;
+    [scale(r), scale(g), scale(b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn diagonal() -> Mask {
+        // a diagonal line and a lone cell
+        Mask::from_fn(Coord::new(-2, -2), Coord::new(2, 2), |v| {
+            [0, 2, 6, 12, 20, 15].contains(&v)
+        })
+    }
+    #[test]
+    fn check_eight() {
+        let comps = Components::label(&diagonal(), Connectivity::Eight);
+        assert_eq!(comps.components.len(), 2);
+        let big = comps.largest(1)[0];
+        assert_eq!(big.size, 5);
+        assert_eq!(big.min, Coord::new(-2, -2));
+        assert_eq!(big.max, Coord::new(2, 2));
+    }
+    #[test]
+    fn check_four() {
+        let comps = Components::label(&diagonal(), Connectivity::Four);
+        assert_eq!(comps.components.len(), 6);
+        let hist = comps.size_histogram();
+        assert_eq!(hist.get(&1), Some(&6));
+    }
+    #[test]
+    fn check_labels() {
+        let mask = diagonal();
+        let comps = Components::label(&mask, Connectivity::Eight);
+        for (i, marked) in mask.cells.iter().enumerate() {
+            assert_eq!(*marked, comps.labels[i] != 0);
+        }
+    }
+    #[cfg(feature = "img")]
+    #[test]
+    fn check_render() {
+        let comps = Components::label(&diagonal(), Connectivity::Eight);
+        let img = comps.render();
+        assert_eq!(img.get_pixel(0, 4), img.get_pixel(4, 0));
+        assert_ne!(img.get_pixel(0, 4), img.get_pixel(1, 0));
+        assert_eq!(img.get_pixel(1, 1), &image::Rgb([0, 0, 0]));
+    }
+}
@@ -0,0 +1,206 @@
+/// Several predicates drawn into one RGBA image, each in its own colour and blend mode.
+use std::error::Error;
+
+use crate::{
+    mask::Mask,
+    predicate::NumberPredicate,
+    ulamspiral_img::{canvas, draw_grid, fill},
+    viewport::Viewport,
+};
+
+/// How a layer's colour combines with what is under it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    /// Paint on top.
+    Over,
+    /// Add the colours, brightening up to white.
+    Add,
+    /// Multiply the colours, so only what both let through shows.
+    Multiply,
+    /// Show each where the other is not, cells in both cancel out.
+    Xor,
+}
+
+/// The cells of one predicate in one colour.
+#[derive(Clone, Copy)]
+pub struct Layer<'a> {
+    pub predicate: &'a dyn NumberPredicate,
+    pub colour: [u8; 3],
+    pub alpha: u8,
+    pub blend: Blend,
+}
+
+impl<'a> Layer<'a> {
+    /// Opaque and painted over the layers before it.
+    pub fn new(predicate: &'a dyn NumberPredicate, colour: [u8; 3]) -> Layer<'a> {
+        Layer {
+            predicate,
+            colour,
+            alpha: 255,
+            blend: Blend::Over,
+        }
+    }
+
+    pub fn with_alpha(self, alpha: u8) -> Layer<'a> {
+        Layer { alpha, ..self }
+    }
+
+    pub fn with_blend(self, blend: Blend) -> Layer<'a> {
+        Layer { blend, ..self }
+    }
+}
+
+/// Draw the layers in order over `background`, which can be transparent.
+/// # Examples
+/// ```
+/// use ulam::{
+///     compose::{compose, Blend, Layer},
+///     predicate::{PerfectSquares, Primes},
+///     viewport::Viewport,
+/// };
+/// let primes = Primes::new();
+/// let layers = [
+///     Layer::new(&primes, [255, 255, 255]),
+///     Layer::new(&PerfectSquares, [255, 0, 0]).with_alpha(128).with_blend(Blend::Add),
+/// ];
+/// let img = compose(&Viewport::new(100, 100), &layers, [0, 0, 0, 0]).unwrap();
+/// assert_eq!(img.dimensions(), (100, 100));
+/// ```
+pub fn compose(
+    viewport: &Viewport,
+    layers: &[Layer],
+    background: [u8; 4],
+) -> Result<image::RgbaImage, Box<dyn Error>> {
+    if viewport.downsample > 1 {
+        return Err("composed renders are not downsampled".into());
+    }
+    let masks: Vec<Mask> = layers
+        .iter()
+        .map(|layer| Mask::from_viewport(viewport, layer.predicate))
+        .collect();
+    let mut img: image::RgbaImage = canvas(viewport)?;
+    for row in 0..viewport.height {
+        for col in 0..viewport.width {
+            let mut pixel = background;
+            for (layer, mask) in layers.iter().zip(&masks) {
+                if mask.get(col, row) {
+                    pixel = blend(pixel, layer);
+                }
+            }
+            fill(&mut img, viewport.scale, col, row, image::Rgba(pixel));
+        }
+    }
+    draw_grid(&mut img, viewport, image::Rgba([64, 64, 64, 255]));
+    Ok(img)
+}
+
+// Put the layer's colour on top of `under`, with straight (not premultiplied) alpha.
+fn blend(under: [u8; 4], layer: &Layer) -> [u8; 4] {
+    let unit = |c: u8| f64::from(c) / 255.0;
+    let (ab, a) = (unit(under[3]), unit(layer.alpha));
+    let (alpha, mixed): (f64, [f64; 3]) = match layer.blend {
+        Blend::Xor => {
+            let alpha = a * (1.0 - ab) + ab * (1.0 - a);
+            let mixed = std::array::from_fn(|i| {
+                unit(layer.colour[i]) * a * (1.0 - ab) + unit(under[i]) * ab * (1.0 - a)
+            });
+            (alpha, mixed)
+        }
+        mode => {
+            let alpha = a + ab * (1.0 - a);
+            let mixed = std::array::from_fn(|i| {
+                let (cb, cs) = (unit(under[i]), unit(layer.colour[i]));
+                let b = match mode {
+                    Blend::Add => (cb + cs).min(1.0),
+                    Blend::Multiply => cb * cs,
+                    _ => cs,
+                };
+                // where there is nothing under it the layer shows its own colour.
+                let cs = (1.0 - ab) * cs + ab * b;
+                cs * a + cb * ab * (1.0 - a)
+            });
+            (alpha, mixed)
+        }
+    };
+    if alpha == 0.0 {
+        return [0, 0, 0, 0];
+    }
+    let byte = |v: f64| (v * 255.0).round().clamp(0.0, 255.0) as u8;
+    [
+        byte(mixed[0] / alpha),
+        byte(mixed[1] / alpha),
+        byte(mixed[2] / alpha),
+        byte(alpha),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::predicate::{PerfectSquares, Primes};
+    fn layer(colour: [u8; 3], alpha: u8, blend: Blend) -> Layer<'static> {
+        Layer::new(&PerfectSquares, colour)
+            .with_alpha(alpha)
+            .with_blend(blend)
+    }
+    #[test]
+    fn check_blend() {
+        let grey = [100, 100, 100, 255];
+        assert_eq!(
+            blend(grey, &layer([200, 0, 0], 255, Blend::Over)),
+            [200, 0, 0, 255]
+        );
+        assert_eq!(blend(grey, &layer([200, 0, 0], 0, Blend::Over)), grey);
+        assert_eq!(
+            blend(grey, &layer([200, 10, 0], 255, Blend::Add)),
+            [255, 110, 100, 255]
+        );
+        assert_eq!(
+            blend(grey, &layer([255, 0, 51], 255, Blend::Multiply)),
+            [100, 0, 20, 255]
+        );
+        // half of red over half of blue
+        let half_blue = [0, 0, 255, 128];
+        let [r, _, b, a] = blend(half_blue, &layer([255, 0, 0], 128, Blend::Over));
+        assert_eq!(a, 192);
+        assert!(r > b);
+        // on nothing every mode gives the layer itself
+        for mode in [Blend::Over, Blend::Add, Blend::Multiply, Blend::Xor] {
+            assert_eq!(
+                blend([0, 0, 0, 0], &layer([10, 20, 30], 255, mode)),
+                [10, 20, 30, 255]
+            );
+        }
+        assert_eq!(
+            blend(grey, &layer([10, 20, 30], 255, Blend::Xor)),
+            [0, 0, 0, 0]
+        );
+    }
+    #[test]
+    fn check_compose() {
+        let viewport = Viewport::new(5, 5).with_scale(2);
+        let primes = Primes::new();
+        let layers = [
+            Layer::new(&primes, [0, 0, 255]),
+            Layer::new(&PerfectSquares, [255, 0, 0]),
+            // odd cells drop out where an earlier layer is, like the square 1 and the prime 7
+            Layer::new(&|n: u64| n % 2 == 1, [0, 255, 0]).with_blend(Blend::Xor),
+        ];
+        let img = compose(&viewport, &layers, [0, 0, 0, 0]).unwrap();
+        let at = |value: u64| {
+            let (col, row) = viewport
+                .cell_of(&crate::calc_coord::calc_coord_u64(value))
+                .unwrap();
+            img.get_pixel(col * 2, row * 2).0
+        };
+        assert_eq!(at(0), [255, 0, 0, 255]);
+        assert_eq!(at(1), [0, 0, 0, 0]);
+        assert_eq!(at(2), [0, 0, 255, 255]);
+        assert_eq!(at(4), [255, 0, 0, 255]);
+        assert_eq!(at(6), [0, 0, 0, 0]);
+        assert_eq!(at(7), [0, 0, 0, 0]);
+        assert_eq!(at(15), [0, 255, 0, 255]);
+        assert_eq!(img.dimensions(), (10, 10));
+        assert!(compose(&viewport.with_downsample(2), &layers, [0; 4]).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+/// Cramér's random model of the primes, used as a baseline to compare the real spiral against.
+///
+/// Every value n marks itself as a "pseudo-prime" with probability 1 / ln n.
+/// The draw for each value comes from a counter based generator keyed by the seed,
+/// so the same seed always marks the same values no matter which window is looked at.
+use crate::{mask::Mask, Coord};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CramerModel {
+    pub seed: u64,
+}
+
+impl CramerModel {
+    pub fn new(seed: u64) -> CramerModel {
+        CramerModel { seed }
+    }
+
+    /// # Examples
+    /// ```
+    /// use ulam::cramer::CramerModel;
+    /// let model = CramerModel::new(42);
+    /// assert!(!model.is_pseudo_prime(1));
+    /// assert!(model.is_pseudo_prime(2));
+    /// assert_eq!(model.is_pseudo_prime(1_000), CramerModel::new(42).is_pseudo_prime(1_000));
+    /// ```
+    pub fn is_pseudo_prime(&self, n: u64) -> bool {
+        if n < 2 {
+            return false;
+        }
+        // 1 / ln 2 is above 1 so 2 is always marked.
+        let probability = 1.0 / (n as f64).ln();
+        self.draw(n) < probability
+    }
+
+    /// Mark the pseudo-primes between `min` and `max` (inclusive).
+    pub fn mask(&self, min: Coord, max: Coord) -> Mask {
+        Mask::from_predicate(min, max, self)
+    }
+
+    /// A uniform number in [0, 1) for the value n.
+    fn draw(&self, n: u64) -> f64 {
+        let bits = splitmix64(self.seed ^ splitmix64(n));
+        (bits >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_density() {
+        let model = CramerModel::new(7);
+        let count = (100_000..110_000)
+            .filter(|n| model.is_pseudo_prime(*n))
+            .count();
+        // 10_000 / ln(105_000) is about 866
+        assert!((780..950).contains(&count), "{count}");
+    }
+    #[test]
+    fn check_seeds() {
+        let a = CramerModel::new(1).mask(Coord::new(-20, -20), Coord::new(20, 20));
+        let b = CramerModel::new(2).mask(Coord::new(-20, -20), Coord::new(20, 20));
+        let a_again = CramerModel::new(1).mask(Coord::new(-20, -20), Coord::new(20, 20));
+        assert_ne!(a, b);
+        assert_eq!(a, a_again);
+    }
+    #[test]
+    fn check_window() {
+        let model = CramerModel::new(3);
+        let big = model.mask(Coord::new(-5, -5), Coord::new(5, 5));
+        let small = model.mask(Coord::new(1, 1), Coord::new(3, 3));
+        for x in 1..=3 {
+            for y in 1..=3 {
+                let c = Coord::new(x, y);
+                assert_eq!(big.contains(&c), small.contains(&c));
+            }
+        }
+    }
+}
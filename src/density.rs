@@ -0,0 +1,171 @@
+/// How densely a predicate (usually the primes) covers each block of a downsampled viewport,
+/// for looking at the spiral zoomed far out.
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calc_coord::calc_coord_u64, predicate::NumberPredicate, value_of_coord_u64, viewport::Viewport,
+};
+
+/// How a density turns into a brightness.
+/// `Log` brings out the sparse blocks far from the center, where primes thin out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToneMap {
+    Linear,
+    Log,
+}
+
+/// One entry per block of `viewport.downsample` cells a side, row by row from the top.
+/// Blocks along the right and bottom edges can be cut short.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DensityGrid {
+    pub width: u32,
+    pub height: u32,
+    /// Cells of each block in the predicate.
+    pub hits: Vec<u64>,
+    /// Cells in each block.
+    pub cells: Vec<u64>,
+}
+
+impl DensityGrid {
+    /// # Examples
+    /// ```
+    /// use ulam::{density::DensityGrid, viewport::Viewport};
+    /// let is_prime = |n: u64| n > 1 && (2..n).all(|d| n % d != 0);
+    /// let viewport = Viewport::new(30, 30).with_downsample(10);
+    /// let grid = DensityGrid::new(&viewport, &is_prime);
+    /// assert_eq!(grid.hits.iter().sum::<u64>(), 154);
+    /// ```
+    pub fn new<P: NumberPredicate + ?Sized>(viewport: &Viewport, predicate: &P) -> DensityGrid {
+        let block = viewport.downsample.max(1);
+        let width = viewport.block_width();
+        let height = viewport.block_height();
+        let mut hits = vec![0; (width * height) as usize];
+        let mut cells = vec![0; (width * height) as usize];
+        let index = |col: u32, row: u32| ((row / block) * width + col / block) as usize;
+
+        for row in 0..viewport.height {
+            for col in (0..viewport.width).step_by(block as usize) {
+                cells[index(col, row)] += u64::from(block.min(viewport.width - col));
+            }
+        }
+
+        if viewport.lists_members() {
+            for value in predicate.members(0, viewport.max_value()) {
+                if let Some((col, row)) = viewport.cell_of(&calc_coord_u64(value)) {
+                    hits[index(col, row)] += 1;
+                }
+            }
+        } else {
+            for row in 0..viewport.height {
+                for col in 0..viewport.width {
+                    if predicate.contains(value_of_coord_u64(&viewport.coord_at(col, row))) {
+                        hits[index(col, row)] += 1;
+                    }
+                }
+            }
+        }
+
+        DensityGrid {
+            width,
+            height,
+            hits,
+            cells,
+        }
+    }
+
+    /// Share of the cells of a block in the predicate, from 0 to 1.
+    pub fn density(&self, x: u32, y: u32) -> f64 {
+        let i = (y * self.width + x) as usize;
+        if self.cells[i] == 0 {
+            0.0
+        } else {
+            self.hits[i] as f64 / self.cells[i] as f64
+        }
+    }
+
+    fn densities(&self) -> Vec<f64> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.density(x, y))
+            .collect()
+    }
+
+    /// A brightness from 0 to 255 for every block, the densest block gets 255.
+    pub fn tone(&self, tone: ToneMap) -> Vec<u8> {
+        let max = self.densities().into_iter().fold(0.0, f64::max);
+        self.tone_up_to(tone, max)
+    }
+
+    /// Like `tone` with a fixed density getting 255 instead of the densest block,
+    /// so grids drawn next to each other share one scale.
+    pub fn tone_up_to(&self, tone: ToneMap, max: f64) -> Vec<u8> {
+        let densities = self.densities();
+        // scale the log so a single hit in the biggest block still shows up.
+        let cells = self.cells.iter().max().copied().unwrap_or(1) as f64;
+        densities
+            .iter()
+            .map(|d| {
+                if max == 0.0 {
+                    return 0;
+                }
+                let level = match tone {
+                    ToneMap::Linear => d / max,
+                    ToneMap::Log => (1.0 + d * cells).ln() / (1.0 + max * cells).ln(),
+                };
+                (level.min(1.0) * 255.0).round() as u8
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Coord;
+    #[test]
+    fn check_blocks() {
+        // 7 x 5 cells in blocks of 3, so the last column and row of blocks are cut short
+        let viewport = Viewport::new(7, 5).with_downsample(3);
+        let grid = DensityGrid::new(&viewport, &|_| true);
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert_eq!(grid.cells, vec![9, 9, 3, 6, 6, 2]);
+        assert_eq!(grid.hits, grid.cells);
+        assert_eq!(grid.tone(ToneMap::Linear), vec![255; 6]);
+    }
+    #[test]
+    fn check_paths_agree() {
+        let odd = |n: u64| n % 2 == 1;
+        let near = Viewport::new(12, 12).with_downsample(4);
+        let far = Viewport::centered_on(Coord::new(500, 0), 12, 12).with_downsample(4);
+        assert!(near.lists_members());
+        assert!(!far.lists_members());
+        for viewport in [near, far] {
+            let grid = DensityGrid::new(&viewport, &odd);
+            for (i, hits) in grid.hits.iter().enumerate() {
+                let (bx, by) = (i as u32 % 3, i as u32 / 3);
+                let mut expected = 0;
+                for row in by * 4..by * 4 + 4 {
+                    for col in bx * 4..bx * 4 + 4 {
+                        expected += value_of_coord_u64(&viewport.coord_at(col, row)) % 2;
+                    }
+                }
+                assert_eq!(*hits, expected);
+            }
+        }
+    }
+    #[test]
+    fn check_tone() {
+        let grid = DensityGrid {
+            width: 3,
+            height: 1,
+            hits: vec![0, 1, 4],
+            cells: vec![4, 4, 4],
+        };
+        assert_eq!(grid.tone(ToneMap::Linear), vec![0, 64, 255]);
+        let log = grid.tone(ToneMap::Log);
+        assert_eq!(log[0], 0);
+        assert!(log[1] > 64);
+        assert_eq!(log[2], 255);
+        assert_eq!(grid.tone_up_to(ToneMap::Linear, 2.0), vec![0, 32, 128]);
+    }
+}
@@ -0,0 +1,235 @@
+/// Finding the biggest stretches of the spiral with no marked cells (usually no primes).
+use crate::{mask::Mask, value_of_coord, Coord, Quad};
+
+/// An axis aligned block of unmarked cells, `min` and `max` are opposite corners.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmptyRegion {
+    pub min: Coord,
+    pub max: Coord,
+    pub min_value: u32,
+    pub max_value: u32,
+}
+
+impl EmptyRegion {
+    fn new(min: Coord, max: Coord) -> EmptyRegion {
+        let mut min_value = u32::MAX;
+        let mut max_value = 0;
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let v = value_of_coord(&Coord::new(x, y));
+                min_value = min_value.min(v);
+                max_value = max_value.max(v);
+            }
+        }
+        EmptyRegion {
+            min,
+            max,
+            min_value,
+            max_value,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        (self.max.x - self.min.x + 1) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.max.y - self.min.y + 1) as u32
+    }
+
+    pub fn area(&self) -> u64 {
+        u64::from(self.width()) * u64::from(self.height())
+    }
+}
+
+/// The longest run of unmarked cells walking out from the center in one direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RayGap {
+    pub direction: Quad,
+    pub start: Coord,
+    pub end: Coord,
+    pub length: u32,
+    pub min_value: u32,
+    pub max_value: u32,
+}
+
+// (col, row) of the top left cell and the size of a region found in the mask.
+fn region_from(mask: &Mask, col: u32, row: u32, width: u32, height: u32) -> EmptyRegion {
+    let top_left = mask.coord_at(col, row);
+    let bottom_right = mask.coord_at(col + width - 1, row + height - 1);
+    EmptyRegion::new(
+        Coord::new(top_left.x, bottom_right.y),
+        Coord::new(bottom_right.x, top_left.y),
+    )
+}
+
+/// The largest square without a single marked cell.
+/// # Examples
+/// ```
+/// use ulam::{gaps, mask::Mask, Coord};
+/// let is_prime = |n: u64| n > 1 && (2..n).all(|d| n % d != 0);
+/// let primes = Mask::from_predicate(Coord::new(-10, -10), Coord::new(10, 10), &is_prime);
+/// let square = gaps::largest_empty_square(&primes).unwrap();
+/// assert!(square.width() >= 2);
+/// ```
+pub fn largest_empty_square(mask: &Mask) -> Option<EmptyRegion> {
+    // side of the largest empty square with its bottom right corner on each cell.
+    let mut sides = vec![0_u32; mask.cells.len()];
+    let mut best: Option<(u32, u32, u32)> = None;
+    for row in 0..mask.height {
+        for col in 0..mask.width {
+            if mask.get(col, row) {
+                continue;
+            }
+            let i = (row * mask.width + col) as usize;
+            let side = if row == 0 || col == 0 {
+                1
+            } else {
+                let w = mask.width as usize;
+                1 + sides[i - 1].min(sides[i - w]).min(sides[i - w - 1])
+            };
+            sides[i] = side;
+            if best.is_none_or(|(_, _, s)| side > s) {
+                best = Some((col + 1 - side, row + 1 - side, side));
+            }
+        }
+    }
+    best.map(|(col, row, side)| region_from(mask, col, row, side, side))
+}
+
+/// The largest rectangle (by area) without a single marked cell.
+pub fn largest_empty_rectangle(mask: &Mask) -> Option<EmptyRegion> {
+    // height of the unmarked run ending on each column of the current row.
+    let mut heights = vec![0_u32; mask.width as usize];
+    let mut best: Option<(u32, u32, u32, u32)> = None;
+    let mut best_area = 0_u64;
+    for row in 0..mask.height {
+        for col in 0..mask.width {
+            heights[col as usize] = if mask.get(col, row) {
+                0
+            } else {
+                heights[col as usize] + 1
+            };
+        }
+        // largest rectangle in the histogram, using a stack of increasing heights.
+        let mut stack: Vec<usize> = Vec::new();
+        for i in 0..=heights.len() {
+            let h = heights.get(i).copied().unwrap_or(0);
+            while let Some(&top) = stack.last() {
+                if heights[top] < h {
+                    break;
+                }
+                stack.pop();
+                let height = heights[top];
+                let left = stack.last().map_or(0, |l| l + 1);
+                let width = (i - left) as u32;
+                let area = u64::from(width) * u64::from(height);
+                if height > 0 && area > best_area {
+                    best_area = area;
+                    best = Some((left as u32, row + 1 - height, width, height));
+                }
+            }
+            stack.push(i);
+        }
+    }
+    best.map(|(col, row, width, height)| region_from(mask, col, row, width, height))
+}
+
+/// For each of the 8 directions out of the center, the longest run of unmarked cells
+/// before leaving the mask. Directions with no unmarked cells are left out.
+pub fn ray_gaps(mask: &Mask) -> Vec<RayGap> {
+    let directions = [
+        (Quad::North, 0, 1),
+        (Quad::NorthEast, 1, 1),
+        (Quad::East, 1, 0),
+        (Quad::SouthEast, 1, -1),
+        (Quad::South, 0, -1),
+        (Quad::SouthWest, -1, -1),
+        (Quad::West, -1, 0),
+        (Quad::NorthWest, -1, 1),
+    ];
+    let mut gaps = Vec::new();
+    for (direction, dx, dy) in directions {
+        let mut best: Option<(u32, u32)> = None;
+        let mut run_start = None;
+        let mut step = 1;
+        loop {
+            let c = Coord::new(dx * step as i32, dy * step as i32);
+            let inside = mask.position_of(&c).is_some();
+            if inside && !mask.contains(&c) {
+                run_start.get_or_insert(step);
+            } else if let Some(start) = run_start.take() {
+                if best.is_none_or(|(s, e)| step - start > e - s + 1) {
+                    best = Some((start, step - 1));
+                }
+            }
+            if !inside {
+                break;
+            }
+            step += 1;
+        }
+        if let Some((start, end)) = best {
+            let at = |s: u32| Coord::new(dx * s as i32, dy * s as i32);
+            // values only grow walking out from the center.
+            gaps.push(RayGap {
+                direction,
+                start: at(start),
+                end: at(end),
+                length: end - start + 1,
+                min_value: value_of_coord(&at(start)),
+                max_value: value_of_coord(&at(end)),
+            });
+        }
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_square() {
+        // only the center row is marked
+        let m = Mask::from_fn(Coord::new(-3, -3), Coord::new(3, 3), |v| {
+            [0, 1, 5, 10, 18, 27, 39].contains(&v)
+        });
+        let sq = largest_empty_square(&m).unwrap();
+        assert_eq!(sq.width(), 3);
+        assert_eq!(sq.height(), 3);
+        assert_eq!(sq.max, Coord::new(-1, 3));
+    }
+    #[test]
+    fn check_rectangle() {
+        let m = Mask::from_fn(Coord::new(-3, -3), Coord::new(3, 3), |v| {
+            [0, 1, 5, 10, 18, 27, 39].contains(&v)
+        });
+        let rect = largest_empty_rectangle(&m).unwrap();
+        assert_eq!(rect.area(), 21);
+        assert_eq!(rect.min, Coord::new(-3, 1));
+        assert_eq!(rect.max, Coord::new(3, 3));
+        assert_eq!(rect.min_value, 2);
+        assert_eq!(rect.max_value, 38);
+    }
+    #[test]
+    fn check_full() {
+        let m = Mask::from_fn(Coord::new(-1, -1), Coord::new(1, 1), |_| true);
+        assert_eq!(largest_empty_square(&m), None);
+        assert_eq!(largest_empty_rectangle(&m), None);
+        assert!(ray_gaps(&m).is_empty());
+    }
+    #[cfg(feature = "prime")]
+    #[test]
+    fn check_rays() {
+        let m = Mask::primes_within(10);
+        let gaps = ray_gaps(&m);
+        // the south east diagonal is 4n^2 + 4n which is never prime
+        let se = gaps
+            .iter()
+            .find(|g| g.direction == Quad::SouthEast)
+            .unwrap();
+        assert_eq!(se.length, 10);
+        assert_eq!(se.start, Coord::new(1, -1));
+        assert_eq!(se.min_value, 8);
+        assert_eq!(se.max_value, 440);
+    }
+}
@@ -0,0 +1,177 @@
+/// Small numbered spirals as text, for handouts and slides: an HTML table,
+/// a Markdown or plain text grid and a TikZ picture.
+use std::fmt::Write;
+
+use crate::{predicate::NumberPredicate, value_of_coord_u64, viewport::Viewport};
+
+/// The numbers of a viewport row by row from the top, and which of them are highlighted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberGrid {
+    pub width: u32,
+    pub height: u32,
+    pub numbers: Vec<u64>,
+    pub highlighted: Vec<bool>,
+}
+
+impl NumberGrid {
+    /// Number the viewport with `start` in the middle of the spiral, so 0 like the rest of
+    /// this crate or 1 like Ulam's own drawing. The predicate sees the numbers as shown.
+    /// # Examples
+    /// ```
+    /// use ulam::{grid_text::NumberGrid, viewport::Viewport};
+    /// let is_prime = |n: u64| n > 1 && (2..n).all(|d| n % d != 0);
+    /// let grid = NumberGrid::new(&Viewport::new(3, 3), 1, &is_prime);
+    /// assert_eq!(grid.to_ascii(), "5* 4  3*\n6  1  2*\n7* 8  9 \n");
+    /// ```
+    pub fn new<P: NumberPredicate + ?Sized>(
+        viewport: &Viewport,
+        start: u64,
+        predicate: &P,
+    ) -> NumberGrid {
+        let mut numbers = Vec::with_capacity((viewport.width * viewport.height) as usize);
+        for row in 0..viewport.height {
+            for col in 0..viewport.width {
+                numbers.push(value_of_coord_u64(&viewport.coord_at(col, row)) + start);
+            }
+        }
+        let highlighted = numbers.iter().map(|n| predicate.contains(*n)).collect();
+        NumberGrid {
+            width: viewport.width,
+            height: viewport.height,
+            numbers,
+            highlighted,
+        }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = (&[u64], &[bool])> {
+        let width = self.width.max(1) as usize;
+        self.numbers
+            .chunks(width)
+            .zip(self.highlighted.chunks(width))
+    }
+
+    fn digits(&self) -> usize {
+        self.numbers.iter().max().map_or(1, |n| n.to_string().len())
+    }
+
+    /// A `<table class="ulam">` with highlighted cells in the `highlight` class.
+    /// No styles are included, those belong in the page.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<table class=\"ulam\">\n");
+        for (numbers, highlighted) in self.rows() {
+            html.push_str("<tr>");
+            for (n, h) in numbers.iter().zip(highlighted) {
+                if *h {
+                    write!(html, "<td class=\"highlight\">{n}</td>").unwrap();
+                } else {
+                    write!(html, "<td>{n}</td>").unwrap();
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+        html
+    }
+
+    /// A Markdown table with highlighted numbers in bold, padded so the columns line up
+    /// in the source too. Markdown tables need a header so it is left empty.
+    pub fn to_markdown(&self) -> String {
+        // room for the `**` on both sides.
+        let width = self.digits() + 4;
+        let mut md = String::new();
+        let line = |cells: &mut dyn Iterator<Item = String>| {
+            let mut s = String::from("|");
+            for cell in cells {
+                write!(s, " {cell:>width$} |").unwrap();
+            }
+            s.push('\n');
+            s
+        };
+        md += &line(&mut (0..self.width).map(|_| String::new()));
+        md += &line(&mut (0..self.width).map(|_| format!("{}:", "-".repeat(width - 1))));
+        for (numbers, highlighted) in self.rows() {
+            md += &line(&mut numbers.iter().zip(highlighted).map(|(n, h)| {
+                if *h {
+                    format!("**{n}**")
+                } else {
+                    n.to_string()
+                }
+            }));
+        }
+        md
+    }
+
+    /// Right aligned numbers, highlighted ones followed by a `*`.
+    pub fn to_ascii(&self) -> String {
+        let width = self.digits();
+        let mut text = String::new();
+        for (numbers, highlighted) in self.rows() {
+            let cells: Vec<String> = numbers
+                .iter()
+                .zip(highlighted)
+                .map(|(n, h)| format!("{n:>width$}{}", if *h { '*' } else { ' ' }))
+                .collect();
+            text.push_str(&cells.join(" "));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// A `tikzpicture` with one square node per cell, highlighted ones shaded and in bold.
+    /// The `cell` and `highlight` styles can be changed from the document.
+    pub fn to_tikz(&self) -> String {
+        let mut tikz = String::from(
+            "\\begin{tikzpicture}[cell/.style={draw, minimum size=1cm}, \
+             highlight/.style={cell, fill=black!20, font=\\bfseries}]\n",
+        );
+        for (row, (numbers, highlighted)) in self.rows().enumerate() {
+            for (col, (n, h)) in numbers.iter().zip(highlighted).enumerate() {
+                let style = if *h { "highlight" } else { "cell" };
+                let y = -(row as i64);
+                writeln!(tikz, "\\node[{style}] at ({col},{y}) {{{n}}};").unwrap();
+            }
+        }
+        tikz.push_str("\\end{tikzpicture}\n");
+        tikz
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn grid() -> NumberGrid {
+        let evens = |n: u64| n.is_multiple_of(2);
+        NumberGrid::new(&Viewport::new(4, 3), 0, &evens)
+    }
+    #[test]
+    fn check_numbers() {
+        let grid = grid();
+        assert_eq!(grid.numbers, vec![4, 3, 2, 11, 5, 0, 1, 10, 6, 7, 8, 9]);
+        assert_eq!(grid.highlighted.iter().filter(|h| **h).count(), 6);
+        let shifted = NumberGrid::new(&Viewport::new(4, 3), 1, &|_| false);
+        assert_eq!(shifted.numbers[5], 1);
+    }
+    #[test]
+    fn check_html() {
+        let html = grid().to_html();
+        assert_eq!(html.matches("<tr>").count(), 3);
+        assert!(html.contains("<tr><td class=\"highlight\">4</td><td>3</td>"));
+    }
+    #[test]
+    fn check_markdown() {
+        let md = grid().to_markdown();
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], "| -----: | -----: | -----: | -----: |");
+        assert_eq!(lines[2], "|  **4** |      3 |  **2** |     11 |");
+        assert!(lines.iter().all(|l| l.len() == lines[0].len()));
+    }
+    #[test]
+    fn check_tikz() {
+        let tikz = grid().to_tikz();
+        assert!(tikz.starts_with("\\begin{tikzpicture}"));
+        assert!(tikz.ends_with("\\end{tikzpicture}\n"));
+        assert!(tikz.contains("\\node[highlight] at (1,-1) {0};"));
+        assert!(tikz.contains("\\node[cell] at (3,-2) {9};"));
+    }
+}
@@ -0,0 +1,196 @@
+/// Any data indexed by value, like events per block or per day, gathered into the cells of a viewport
+/// and coloured with a colormap.
+#[cfg(feature = "img")]
+use std::error::Error;
+
+use crate::{calc_coord::calc_coord_u64, viewport::Viewport};
+#[cfg(feature = "img")]
+use crate::{density::ToneMap, palette::Colormap};
+
+/// How the weights landing in one block become one number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reducer {
+    Sum,
+    Mean,
+    Max,
+}
+
+/// One entry per block of `viewport.downsample` cells a side, row by row from the top,
+/// the same layout as `DensityGrid`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+    pub width: u32,
+    pub height: u32,
+    pub reducer: Reducer,
+    /// The sum or the max of the weights in each block.
+    pub totals: Vec<f64>,
+    /// Weights in each block.
+    pub counts: Vec<u64>,
+}
+
+impl Heatmap {
+    /// Gather `(value, weight)` pairs, in any order. Values out of view are skipped
+    /// and a value can come up more than once.
+    /// # Examples
+    /// ```
+    /// use ulam::{heatmap::{Heatmap, Reducer}, viewport::Viewport};
+    /// let events = [(0, 2.0), (1, 3.0), (1, 1.0), (1_000, 9.0)];
+    /// let heat = Heatmap::from_pairs(&Viewport::new(3, 3), events, Reducer::Sum);
+    /// // 1 is right of 0 in the middle row
+    /// assert_eq!(heat.get(1, 1), Some(2.0));
+    /// assert_eq!(heat.get(2, 1), Some(4.0));
+    /// assert_eq!(heat.get(0, 0), None);
+    /// ```
+    pub fn from_pairs<I>(viewport: &Viewport, pairs: I, reducer: Reducer) -> Heatmap
+    where
+        I: IntoIterator<Item = (u64, f64)>,
+    {
+        let block = viewport.downsample.max(1);
+        let width = viewport.block_width();
+        let height = viewport.block_height();
+        let start = match reducer {
+            Reducer::Max => f64::NEG_INFINITY,
+            _ => 0.0,
+        };
+        let mut totals = vec![start; (width * height) as usize];
+        let mut counts = vec![0; (width * height) as usize];
+        let max_value = viewport.max_value();
+        for (value, weight) in pairs {
+            if value > max_value {
+                continue;
+            }
+            let Some((col, row)) = viewport.cell_of(&calc_coord_u64(value)) else {
+                continue;
+            };
+            let i = ((row / block) * width + col / block) as usize;
+            totals[i] = match reducer {
+                Reducer::Max => totals[i].max(weight),
+                _ => totals[i] + weight,
+            };
+            counts[i] += 1;
+        }
+        Heatmap {
+            width,
+            height,
+            reducer,
+            totals,
+            counts,
+        }
+    }
+
+    /// Gather a slice holding the weight of each value from 0 up.
+    pub fn from_slice(viewport: &Viewport, weights: &[f64], reducer: Reducer) -> Heatmap {
+        let in_view = weights.len().min(
+            usize::try_from(viewport.max_value())
+                .unwrap_or(usize::MAX)
+                .saturating_add(1),
+        );
+        let pairs = weights[..in_view]
+            .iter()
+            .enumerate()
+            .map(|(value, weight)| (value as u64, *weight));
+        Heatmap::from_pairs(viewport, pairs, reducer)
+    }
+
+    /// The reduced weight of a block, `None` when nothing landed in it.
+    pub fn get(&self, x: u32, y: u32) -> Option<f64> {
+        let i = (y * self.width + x) as usize;
+        match (self.counts[i], self.reducer) {
+            (0, _) => None,
+            (count, Reducer::Mean) => Some(self.totals[i] / count as f64),
+            _ => Some(self.totals[i]),
+        }
+    }
+
+    /// The smallest and largest reduced weights, `None` when there are none.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).filter_map(move |x| self.get(x, y)))
+            .fold(None, |range, v| match range {
+                None => Some((v, v)),
+                Some((lo, hi)) => Some((f64::min(lo, v), f64::max(hi, v))),
+            })
+    }
+
+    /// Colour every block from its weight across the whole range, `scale` pixels a side.
+    /// Empty blocks are black.
+    #[cfg(feature = "img")]
+    pub fn render(
+        &self,
+        scale: u32,
+        colormap: Colormap,
+        tone: ToneMap,
+    ) -> Result<image::RgbImage, Box<dyn Error>> {
+        self.render_in(scale, colormap, tone, self.range().unwrap_or((0.0, 0.0)))
+    }
+
+    /// Like `render` with a fixed `(low, high)` range, so heatmaps drawn next to each other
+    /// share one scale. Weights outside it take the colour at its ends.
+    #[cfg(feature = "img")]
+    pub fn render_in(
+        &self,
+        scale: u32,
+        colormap: Colormap,
+        tone: ToneMap,
+        (low, high): (f64, f64),
+    ) -> Result<image::RgbImage, Box<dyn Error>> {
+        let width = self.width.checked_mul(scale).ok_or("image too wide")?;
+        let height = self.height.checked_mul(scale).ok_or("image too tall")?;
+        let spread = high - low;
+        let level = |v: f64| {
+            if spread <= 0.0 {
+                return 1.0;
+            }
+            let above = (v - low).max(0.0);
+            match tone {
+                ToneMap::Linear => above / spread,
+                ToneMap::Log => (1.0 + above).ln() / (1.0 + spread).ln(),
+            }
+        };
+        Ok(image::RgbImage::from_fn(width, height, |x, y| {
+            match self.get(x / scale, y / scale) {
+                Some(v) => image::Rgb(colormap.sample(level(v))),
+                None => image::Rgb([0, 0, 0]),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_reducers() {
+        // 0 to 8 fill a 3 x 3 view, one block of 2 x 2 covers 4, 3, 5 and 0
+        let viewport = Viewport::new(3, 3).with_downsample(2);
+        let weights: Vec<f64> = (0..20).map(f64::from).collect();
+        let sum = Heatmap::from_slice(&viewport, &weights, Reducer::Sum);
+        assert_eq!((sum.width, sum.height), (2, 2));
+        assert_eq!(sum.counts, vec![4, 2, 2, 1]);
+        assert_eq!(sum.get(0, 0), Some(4.0 + 3.0 + 5.0 + 0.0));
+        let mean = Heatmap::from_slice(&viewport, &weights, Reducer::Mean);
+        assert_eq!(mean.get(0, 0), Some(3.0));
+        let max = Heatmap::from_slice(&viewport, &weights, Reducer::Max);
+        assert_eq!(max.get(0, 0), Some(5.0));
+        assert_eq!(max.range(), Some((2.0, 8.0)));
+        let none = Heatmap::from_pairs(&viewport, [(100, 1.0)], Reducer::Max);
+        assert_eq!(none.range(), None);
+        assert_eq!(none.get(1, 1), None);
+    }
+    #[test]
+    #[cfg(feature = "img")]
+    fn check_render() {
+        let viewport = Viewport::new(3, 3);
+        let heat = Heatmap::from_pairs(&viewport, [(0, 1.0), (1, 5.0), (2, 3.0)], Reducer::Sum);
+        let img = heat.render(2, Colormap::Viridis, ToneMap::Linear).unwrap();
+        assert_eq!(img.dimensions(), (6, 6));
+        assert_eq!(img.get_pixel(2, 2).0, Colormap::Viridis.sample(0.0));
+        assert_eq!(img.get_pixel(5, 3).0, Colormap::Viridis.sample(1.0));
+        assert_eq!(img.get_pixel(5, 1).0, Colormap::Viridis.sample(0.5));
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0]);
+        let fixed = heat
+            .render_in(1, Colormap::Magma, ToneMap::Log, (0.0, 3.0))
+            .unwrap();
+        assert_eq!(fixed.get_pixel(2, 0).0, Colormap::Magma.sample(1.0));
+    }
+}
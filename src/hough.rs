@@ -0,0 +1,345 @@
+/// Finding the straight lines in a mask with a Hough transform, whatever their slope,
+/// and naming the quadratic behind the ones that follow the spiral's diagonals or axes.
+/// `Mask::from_viewport` gives the same cells `ulamspiral_img::render` draws.
+use serde::{Deserialize, Serialize};
+
+use crate::{mask::Mask, value_of_coord_u64, Coord};
+
+/// Votes for every line through the marked cells of a mask. A line is the set of points
+/// with `x cos(angle) + y sin(angle) = offset`, in cell coordinates around 0 with y going up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    /// Angles tried, evenly over half a turn.
+    pub angles: u32,
+    /// Offsets are counted in bins this wide.
+    pub offset_step: f64,
+    /// Bins of offset either side of 0.
+    pub half_offsets: u32,
+    /// One row of offset bins per angle.
+    pub votes: Vec<u32>,
+}
+
+/// A line from the accumulator, angle in degrees from 0 up to 180.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HoughLine {
+    pub angle: f64,
+    pub offset: f64,
+    pub votes: u32,
+    /// For lines along an axis or a diagonal, the quadratic along each way out from the center.
+    pub quadratics: Vec<Quadratic>,
+}
+
+/// The values on a ray of cells going out by `direction` each step are `a k^2 + b k + c`,
+/// where `k` is the ring of the cell, from ring `from_ring` on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quadratic {
+    pub direction: (i32, i32),
+    pub from_ring: u32,
+    pub a: i64,
+    pub b: i64,
+    pub c: i64,
+}
+
+impl Quadratic {
+    pub fn value(&self, ring: u32) -> i64 {
+        let k = i64::from(ring);
+        self.a * k * k + self.b * k + self.c
+    }
+}
+
+/// The `count` strongest lines in the mask, trying every degree and offsets in half cells,
+/// which keeps neighbouring diagonals apart.
+/// # Examples
+/// ```
+/// use ulam::{hough::strongest_lines, mask::Mask, Coord};
+/// // a lone diagonal, through (2, 0) and (0, 2)
+/// let mask = Mask::from_fn(Coord::new(-20, -20), Coord::new(20, 20), |v| {
+///     let c = ulam::calc_coord::calc_coord_u64(v);
+///     c.x + c.y == 2
+/// });
+/// let line = &strongest_lines(&mask, 1)[0];
+/// assert_eq!((line.angle, line.votes), (45.0, 39));
+/// // going south east 10, 26, 50, ... are 4k^2 - 4k + 2
+/// let south_east = line.quadratics.iter().find(|q| q.direction == (1, -1)).unwrap();
+/// assert_eq!((south_east.a, south_east.b, south_east.c), (4, -4, 2));
+/// ```
+pub fn strongest_lines(mask: &Mask, count: usize) -> Vec<HoughLine> {
+    Accumulator::new(mask, 180, 0.5).peaks(count)
+}
+
+impl Accumulator {
+    /// Every marked cell votes once at each angle for the offset bin of the line through it.
+    ///
+    /// # Panics
+    /// If `offset_step` is not a positive finite number.
+    pub fn new(mask: &Mask, angles: u32, offset_step: f64) -> Accumulator {
+        assert!(
+            offset_step > 0.0 && offset_step.is_finite(),
+            "offset_step must be positive and finite"
+        );
+        let angles = angles.max(1);
+        let far = [
+            mask.top_left,
+            mask.bottom_right(),
+            Coord::new(mask.top_left.x, mask.bottom_right().y),
+            Coord::new(mask.bottom_right().x, mask.top_left.y),
+        ]
+        .iter()
+        .map(|c| f64::from(c.x).hypot(f64::from(c.y)))
+        .fold(0.0, f64::max);
+        let half_offsets = (far / offset_step).ceil() as u32 + 1;
+        let bins = 2 * half_offsets as usize + 1;
+        let mut votes = vec![0; angles as usize * bins];
+        let trig: Vec<(f64, f64)> = (0..angles)
+            .map(|i| angle_of(i, angles).to_radians().sin_cos())
+            .map(|(sin, cos)| (cos, sin))
+            .collect();
+        for row in 0..mask.height {
+            for col in 0..mask.width {
+                if !mask.get(col, row) {
+                    continue;
+                }
+                let c = mask.coord_at(col, row);
+                let (x, y) = (f64::from(c.x), f64::from(c.y));
+                for (i, (cos, sin)) in trig.iter().enumerate() {
+                    let bin = ((x * cos + y * sin) / offset_step).round() as i64;
+                    votes[i * bins + (bin + i64::from(half_offsets)) as usize] += 1;
+                }
+            }
+        }
+        Accumulator {
+            angles,
+            offset_step,
+            half_offsets,
+            votes,
+        }
+    }
+
+    fn bins(&self) -> usize {
+        2 * self.half_offsets as usize + 1
+    }
+
+    pub fn votes_at(&self, angle: u32, bin: i64) -> u32 {
+        let i = bin + i64::from(self.half_offsets);
+        if angle >= self.angles || i < 0 || i as usize >= self.bins() {
+            return 0;
+        }
+        self.votes[angle as usize * self.bins() + i as usize]
+    }
+
+    /// The `count` lines with the most votes, strongest first. Only the local peaks count,
+    /// so one thick line is not reported over and over. The last angle neighbours the first,
+    /// with the offset negated, as the line at 180 degrees is the one at 0 the other way round.
+    pub fn peaks(&self, count: usize) -> Vec<HoughLine> {
+        let half = i64::from(self.half_offsets);
+        let mut peaks = Vec::new();
+        for angle in 0..self.angles {
+            for bin in -half..=half {
+                let votes = self.votes_at(angle, bin);
+                if votes == 0 {
+                    continue;
+                }
+                // ties go to the first of them, so a flat top is one peak.
+                let is_peak = [-1i64, 0, 1].iter().all(|da| {
+                    [-1i64, 0, 1].iter().all(|db| {
+                        let (a, b) = self.wrap(i64::from(angle) + da, bin + db);
+                        if (a, b) == (angle, bin) {
+                            return true;
+                        }
+                        let other = self.votes_at(a, b);
+                        if (a, b) < (angle, bin) {
+                            other < votes
+                        } else {
+                            other <= votes
+                        }
+                    })
+                });
+                if is_peak {
+                    peaks.push((votes, angle, bin));
+                }
+            }
+        }
+        peaks.sort_by(|a, b| b.0.cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+        peaks
+            .into_iter()
+            .take(count)
+            .map(|(votes, angle, bin)| {
+                let offset = bin as f64 * self.offset_step;
+                HoughLine {
+                    angle: angle_of(angle, self.angles),
+                    offset,
+                    votes,
+                    quadratics: quadratics(angle_of(angle, self.angles), offset),
+                }
+            })
+            .collect()
+    }
+
+    // The angle index and bin of a line, with angles before the first or past the last
+    // brought back around.
+    fn wrap(&self, angle: i64, bin: i64) -> (u32, i64) {
+        let angles = i64::from(self.angles);
+        if (0..angles).contains(&angle) {
+            (angle as u32, bin)
+        } else {
+            (angle.rem_euclid(angles) as u32, -bin)
+        }
+    }
+}
+
+fn angle_of(i: u32, angles: u32) -> f64 {
+    f64::from(i) * 180.0 / f64::from(angles)
+}
+
+// The quadratics along a line that runs through cell centres along an axis or a diagonal.
+fn quadratics(angle: f64, offset: f64) -> Vec<Quadratic> {
+    // a point of the line on a cell centre and the way the line runs.
+    let (base, direction) = match angle {
+        0.0 => (Coord::new(offset.round() as i32, 0), (0, 1)),
+        90.0 => (Coord::new(0, offset.round() as i32), (1, 0)),
+        45.0 => (
+            Coord::new((offset * std::f64::consts::SQRT_2).round() as i32, 0),
+            (1, -1),
+        ),
+        135.0 => (
+            Coord::new(0, (offset * std::f64::consts::SQRT_2).round() as i32),
+            (1, 1),
+        ),
+        _ => return Vec::new(),
+    };
+    [direction, (-direction.0, -direction.1)]
+        .into_iter()
+        .filter_map(|d| ray(base, d))
+        .collect()
+}
+
+// Fit the quadratic to a ray far enough out to stay on one arm of every ring, then check it.
+fn ray(base: Coord, (dx, dy): (i32, i32)) -> Option<Quadratic> {
+    let ring = |c: &Coord| c.x.unsigned_abs().max(c.y.unsigned_abs());
+    let start = ring(&base) as i32 + 2;
+    let at = |n: i32| Coord::new(base.x + dx * n, base.y + dy * n);
+    let rings: Vec<i64> = (start..start + 4)
+        .map(|n| i64::from(ring(&at(n))))
+        .collect();
+    let values: Vec<i64> = (start..start + 4)
+        .map(|n| value_of_coord_u64(&at(n)) as i64)
+        .collect();
+    if rings.windows(2).any(|w| w[1] != w[0] + 1) {
+        return None;
+    }
+    // second differences give a, the first difference gives b, then c.
+    let a = (values[2] - 2 * values[1] + values[0]) / 2;
+    let k = rings[0];
+    let b = values[1] - values[0] - a * (2 * k + 1);
+    let c = values[0] - a * k * k - b * k;
+    let quadratic = Quadratic {
+        direction: (dx, dy),
+        from_ring: k as u32,
+        a,
+        b,
+        c,
+    };
+    // the fit holds from as close in as it does.
+    let mut from = quadratic;
+    let mut n = start - 1;
+    while n >= 0 && ring(&at(n)) + 1 == from.from_ring {
+        let earlier = Quadratic {
+            from_ring: from.from_ring - 1,
+            ..from
+        };
+        if earlier.value(earlier.from_ring) != value_of_coord_u64(&at(n)) as i64 {
+            break;
+        }
+        from = earlier;
+        n -= 1;
+    }
+    (quadratic.value(rings[3] as u32) == values[3]).then_some(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc_coord::calc_coord_u64;
+    fn line_mask(on: impl Fn(Coord) -> bool) -> Mask {
+        Mask::from_fn(Coord::new(-30, -30), Coord::new(30, 30), |v| {
+            on(calc_coord_u64(v))
+        })
+    }
+    #[test]
+    fn check_single_lines() {
+        let diagonal = strongest_lines(&line_mask(|c| c.y == c.x), 1);
+        assert_eq!(diagonal[0].angle, 135.0);
+        assert_eq!(diagonal[0].offset, 0.0);
+        assert_eq!(diagonal[0].votes, 61);
+        // the north east diagonal is 4k^2 - 2k, the south west 4k^2 + 2k
+        let quadratics = &diagonal[0].quadratics;
+        assert_eq!(quadratics.len(), 2);
+        for q in quadratics {
+            let expected = if q.direction == (1, 1) { -2 } else { 2 };
+            assert_eq!((q.a, q.b, q.c, q.from_ring), (4, expected, 0, 0));
+            for k in q.from_ring..30 {
+                let c = Coord::new(q.direction.0 * k as i32, q.direction.1 * k as i32);
+                assert_eq!(q.value(k), value_of_coord_u64(&c) as i64);
+            }
+        }
+
+        let column = strongest_lines(&line_mask(|c| c.x == -4), 1);
+        assert_eq!((column[0].angle, column[0].offset), (0.0, -4.0));
+        for q in &column[0].quadratics {
+            for k in q.from_ring..30 {
+                let c = Coord::new(-4, q.direction.1 * k as i32);
+                assert_eq!(q.value(k), value_of_coord_u64(&c) as i64, "{q:?}");
+            }
+        }
+
+        // a slope of 2 follows no quadratic, its normal is near 153 degrees
+        let steep = strongest_lines(&line_mask(|c| c.y == 2 * c.x + 1), 1);
+        assert!((steep[0].angle - 153.0).abs() <= 1.0);
+        assert!(steep[0].quadratics.is_empty());
+    }
+    #[test]
+    fn check_peaks() {
+        let two = line_mask(|c| c.y == 3 || c.x == c.y + 5);
+        let lines = strongest_lines(&two, 2);
+        assert_eq!(lines.len(), 2);
+        let mut found: Vec<(f64, u32)> = lines.iter().map(|l| (l.angle, l.votes)).collect();
+        found.sort_by(|a, b| a.0.total_cmp(&b.0));
+        assert_eq!(found, vec![(90.0, 61), (135.0, 56)]);
+        assert!(strongest_lines(&line_mask(|_| false), 3).is_empty());
+    }
+    #[test]
+    fn check_wrap() {
+        // just past upright, the line peaks at 179 degrees and not again at 0
+        let leaning = line_mask(|c| c.x == 4 + c.y.div_euclid(40));
+        let lines = strongest_lines(&leaning, 3);
+        assert_eq!((lines[0].angle, lines[0].offset), (179.0, -3.5));
+        assert!(lines.iter().all(|l| l.angle != 0.0), "{lines:?}");
+
+        // the last angle at bin -1 touches the first at bin 1
+        let mut accumulator = Accumulator {
+            angles: 4,
+            offset_step: 1.0,
+            half_offsets: 2,
+            votes: vec![0; 4 * 5],
+        };
+        accumulator.votes[2 + 1] = 5;
+        accumulator.votes[3 * 5 + 2 - 1] = 5;
+        let peaks = accumulator.peaks(2);
+        assert_eq!(peaks.len(), 1);
+        assert_eq!((peaks[0].angle, peaks[0].offset), (0.0, 1.0));
+
+        for step in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let new = std::panic::catch_unwind(|| Accumulator::new(&leaning, 180, step));
+            assert!(new.is_err(), "{step}");
+        }
+    }
+    #[test]
+    #[cfg(feature = "prime")]
+    fn check_primes() {
+        let lines = strongest_lines(&Mask::primes_within(60), 10);
+        assert_eq!(lines.len(), 10);
+        assert!(lines.windows(2).all(|w| w[0].votes >= w[1].votes));
+        // the prime diagonals are what stands out
+        assert!(lines.iter().any(|l| !l.quadratics.is_empty()));
+    }
+}
@@ -1,9 +1,45 @@
 #[cfg(feature = "prime")]
 pub mod prime;
+#[cfg(feature = "prime")]
+pub mod sieve;
 #[cfg(feature = "img")]
 pub mod ulamspiral_img;
 
+#[cfg(feature = "img")]
+pub mod animation;
 pub mod calc_coord;
+pub mod cluster;
+#[cfg(feature = "img")]
+pub mod compose;
+pub mod cramer;
+pub mod density;
+pub mod gaps;
+pub mod grid_text;
+pub mod heatmap;
+pub mod hough;
+pub mod mask;
+#[cfg(all(feature = "img", feature = "prime"))]
+pub mod metadata;
+#[cfg(feature = "img")]
+pub mod overlay;
+pub mod palette;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod predicate;
+pub mod prime_neighbours;
+pub mod projection;
+pub mod raster;
+pub mod sequence;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod spectrum;
+#[cfg(feature = "img")]
+pub mod stream;
+pub mod svg;
+pub mod terminal;
+#[cfg(feature = "img")]
+pub mod tiles;
+pub mod viewport;
 
 use crate::calc_coord::calc_coord;
 use serde::{Deserialize, Serialize};
@@ -25,7 +61,7 @@ pub enum Quad {
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct UlamPoint {
-    pub value: u32,
+    pub value: u64,
     pub quad: Quad,
     pub is_prime: bool,
 }
@@ -40,6 +76,20 @@ impl Coord {
     pub fn new(x: i32, y: i32) -> Coord {
         Coord { x, y }
     }
+
+    /// The 8 surrounding coordinates, clockwise starting from the one above.
+    pub fn neighbours(&self) -> [Coord; 8] {
+        [
+            Coord::new(self.x, self.y + 1),
+            Coord::new(self.x + 1, self.y + 1),
+            Coord::new(self.x + 1, self.y),
+            Coord::new(self.x + 1, self.y - 1),
+            Coord::new(self.x, self.y - 1),
+            Coord::new(self.x - 1, self.y - 1),
+            Coord::new(self.x - 1, self.y),
+            Coord::new(self.x - 1, self.y + 1),
+        ]
+    }
 }
 
 #[allow(clippy::comparison_chain)]
@@ -137,6 +187,29 @@ pub fn value_of_coord(c: &Coord) -> u32 {
     }
 }
 
+/// Same as `value_of_coord` but without overflowing for coordinates more than 23,170 cells out.
+/// # Examples
+/// ```
+/// use ulam::{Coord, value_of_coord_u64};
+/// let result = ulam::value_of_coord_u64(&Coord::new(-100_000, 100_000));
+/// assert_eq!(result, 40_000_000_000);
+/// ```
+pub fn value_of_coord_u64(c: &Coord) -> u64 {
+    let (x, y) = (i64::from(c.x), i64::from(c.y));
+    let q = quad_of_coord(c);
+    (match q {
+        Quad::North => 4 * (y * y) - y + (-x),
+        Quad::East => 4 * (x * x) - (3 * x) + y,
+        Quad::South => 4 * (y * y) + (3 * -y) + x,
+        Quad::West => 4 * (x * x) + (-x) + (-y),
+        Quad::NorthWest => 4 * (x * x),
+        Quad::NorthEast => 4 * (x * x) - 2 * x,
+        Quad::SouthWest => 4 * (x * x) + 2 * x.abs(),
+        Quad::SouthEast => 4 * (x * x) + 4 * x,
+        Quad::Center => 0,
+    }) as u64
+}
+
 /// Get the value from the ulam spiral given a Quad and a Coord.
 /// # Examples
 /// ```
@@ -218,7 +291,20 @@ pub fn quad_of_value(v: u32) -> Quad {
     quad_of_coord(&calc_coord(v))
 }
 
+/// Get the values of the 8 cells surrounding a value on the spiral,
+/// clockwise starting from the one above.
+/// # Examples
+/// ```
+/// use ulam::neighbours_of_value;
+/// let result = ulam::neighbours_of_value(0);
+/// assert_eq!(result, [3, 2, 1, 8, 7, 6, 5, 4]);
+/// ```
+pub fn neighbours_of_value(v: u32) -> [u32; 8] {
+    calc_coord(v).neighbours().map(|c| value_of_coord(&c))
+}
+
 #[cfg(test)]
+#[allow(clippy::empty_docs)]
 mod tests {
     use super::*;
     ///
@@ -308,4 +394,22 @@ mod tests {
         let result = value_of_coord(&c1);
         assert_eq!(result, 3990755);
     }
+    #[test]
+    fn check_wide_val() {
+        for x in -30..30 {
+            for y in -30..30 {
+                let c1 = Coord::new(x, y);
+                assert_eq!(value_of_coord_u64(&c1), u64::from(value_of_coord(&c1)));
+            }
+        }
+        let c1 = Coord::new(50_000, -3);
+        assert_eq!(value_of_coord_u64(&c1), 9_999_849_997);
+    }
+    #[test]
+    fn check_neighbours() {
+        let result = neighbours_of_value(2);
+        assert_eq!(result, [13, 12, 11, 10, 1, 0, 3, 14]);
+        let result = neighbours_of_value(27);
+        assert_eq!(result, [28, 53, 52, 51, 26, 9, 10, 11]);
+    }
 }
@@ -0,0 +1,217 @@
+/// A rectangular window of the ulam spiral where each cell is either marked or not.
+use crate::{
+    calc_coord::calc_coord_u64, predicate::NumberPredicate, value_of_coord_u64, viewport::Viewport,
+    Coord,
+};
+
+/// Cells are stored row by row starting from the top (largest y), the same way
+/// the images from `ulamspiral_img` are laid out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mask {
+    pub top_left: Coord,
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<bool>,
+}
+
+impl Mask {
+    /// Build a mask for every cell between `min` and `max` (inclusive),
+    /// marking the cells whose spiral value passes `f`.
+    ///
+    /// # Panics
+    /// If the window has more cells than fit in memory.
+    /// # Examples
+    /// ```
+    /// use ulam::{mask::Mask, Coord};
+    /// let evens = Mask::from_fn(Coord::new(-1, -1), Coord::new(1, 1), |v| v % 2 == 0);
+    /// assert_eq!(evens.count(), 5);
+    /// ```
+    pub fn from_fn<F: Fn(u64) -> bool>(min: Coord, max: Coord, f: F) -> Mask {
+        let (top_left, width, height) = window(min, max);
+        let mut cells = Vec::with_capacity(cell_count(width, height));
+        for row in 0..height {
+            for col in 0..width {
+                let c = Coord::new(top_left.x + col as i32, top_left.y - row as i32);
+                cells.push(f(value_of_coord_u64(&c)));
+            }
+        }
+        Mask {
+            top_left,
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Mark the cells between `min` and `max` (inclusive) whose value is in the predicate.
+    pub fn from_predicate<P: NumberPredicate + ?Sized>(
+        min: Coord,
+        max: Coord,
+        predicate: &P,
+    ) -> Mask {
+        Mask::from_fn(min, max, |v| predicate.contains(v))
+    }
+
+    /// Mark the cells of a viewport whose value is in the predicate.
+    pub fn from_viewport<P: NumberPredicate + ?Sized>(viewport: &Viewport, predicate: &P) -> Mask {
+        let mut mask = Mask {
+            top_left: viewport.top_left(),
+            width: viewport.width,
+            height: viewport.height,
+            cells: vec![false; cell_count(viewport.width, viewport.height)],
+        };
+        if viewport.lists_members() {
+            for value in predicate.members(0, viewport.max_value()) {
+                if let Some((col, row)) = viewport.cell_of(&calc_coord_u64(value)) {
+                    let i = mask.index(col, row);
+                    mask.cells[i] = true;
+                }
+            }
+        } else {
+            for (i, cell) in mask.cells.iter_mut().enumerate() {
+                let c = viewport.coord_at(i as u32 % viewport.width, i as u32 / viewport.width);
+                *cell = predicate.contains(value_of_coord_u64(&c));
+            }
+        }
+        mask
+    }
+
+    /// Mark the primes between `min` and `max` (inclusive). Windows far from the center
+    /// are tested cell by cell rather than sieved all the way out.
+    #[cfg(feature = "prime")]
+    pub fn primes(min: Coord, max: Coord) -> Mask {
+        let (top_left, width, height) = window(min, max);
+        let viewport = Viewport::from_top_left(top_left, width, height);
+        Mask::from_viewport(&viewport, &crate::predicate::Primes::new())
+    }
+
+    /// Mark the primes in the square of the given radius around the center.
+    #[cfg(feature = "prime")]
+    pub fn primes_within(radius: u32) -> Mask {
+        let r = radius as i32;
+        Mask::primes(Coord::new(-r, -r), Coord::new(r, r))
+    }
+
+    /// The coordinate of the bottom right cell.
+    pub fn bottom_right(&self) -> Coord {
+        Coord::new(
+            self.top_left.x + self.width as i32 - 1,
+            self.top_left.y - self.height as i32 + 1,
+        )
+    }
+
+    pub fn get(&self, col: u32, row: u32) -> bool {
+        self.cells[self.index(col, row)]
+    }
+
+    fn index(&self, col: u32, row: u32) -> usize {
+        row as usize * self.width as usize + col as usize
+    }
+
+    pub fn coord_at(&self, col: u32, row: u32) -> Coord {
+        Coord::new(self.top_left.x + col as i32, self.top_left.y - row as i32)
+    }
+
+    /// The (col, row) of a coordinate, or `None` if it lies outside the mask.
+    pub fn position_of(&self, c: &Coord) -> Option<(u32, u32)> {
+        let col = c.x - self.top_left.x;
+        let row = self.top_left.y - c.y;
+        if col < 0 || row < 0 || col >= self.width as i32 || row >= self.height as i32 {
+            None
+        } else {
+            Some((col as u32, row as u32))
+        }
+    }
+
+    /// Whether the cell at a coordinate is marked. Cells outside the mask are not.
+    pub fn contains(&self, c: &Coord) -> bool {
+        self.position_of(c)
+            .map(|(col, row)| self.get(col, row))
+            .unwrap_or(false)
+    }
+
+    /// Number of marked cells.
+    pub fn count(&self) -> usize {
+        self.cells.iter().filter(|m| **m).count()
+    }
+}
+
+// The top left corner, width and height of the window between `min` and `max`.
+fn window(min: Coord, max: Coord) -> (Coord, u32, u32) {
+    let span = |low: i32, high: i32| {
+        u32::try_from((i64::from(high) - i64::from(low) + 1).max(0)).expect("mask too big")
+    };
+    (
+        Coord::new(min.x, max.y),
+        span(min.x, max.x),
+        span(min.y, max.y),
+    )
+}
+
+fn cell_count(width: u32, height: u32) -> usize {
+    (width as usize)
+        .checked_mul(height as usize)
+        .expect("mask too big")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_layout() {
+        let m = Mask::from_fn(Coord::new(-1, -1), Coord::new(1, 1), |v| v == 4);
+        assert_eq!(m.width, 3);
+        assert_eq!(m.height, 3);
+        assert!(m.get(0, 0));
+        assert!(m.contains(&Coord::new(-1, 1)));
+        assert_eq!(m.count(), 1);
+        assert_eq!(m.coord_at(2, 2), Coord::new(1, -1));
+        assert_eq!(m.bottom_right(), Coord::new(1, -1));
+        assert_eq!(m.position_of(&Coord::new(2, 0)), None);
+    }
+    #[cfg(feature = "prime")]
+    #[test]
+    fn check_primes() {
+        let m = Mask::primes_within(2);
+        // 2 3 5 7 11 13 17 19 23
+        assert_eq!(m.count(), 9);
+        assert!(m.contains(&Coord::new(1, 1)));
+        assert!(!m.contains(&Coord::new(0, 0)));
+
+        // a small window a million rings out, past u32 values
+        let (min, max) = (Coord::new(1_000_000, -5), Coord::new(1_000_009, 4));
+        let far = Mask::primes(min, max);
+        let expected = Mask::from_fn(min, max, primal::is_prime);
+        assert_eq!(far, expected);
+        assert!(far.count() > 0);
+    }
+    #[test]
+    fn check_far() {
+        // values here are past u32
+        let m = Mask::from_fn(Coord::new(40_000, -1), Coord::new(40_001, 1), |v| {
+            v > u64::from(u32::MAX)
+        });
+        assert_eq!((m.width, m.height, m.count()), (2, 3, 6));
+        let at = |v: u64| calc_coord_u64(v) == Coord::new(40_000, 0);
+        let m = Mask::from_fn(Coord::new(40_000, -1), Coord::new(40_001, 1), at);
+        assert!(m.contains(&Coord::new(40_000, 0)));
+        assert_eq!(m.count(), 1);
+    }
+    #[test]
+    fn check_viewport() {
+        let odd = |n: u64| n % 2 == 1;
+        let near = Viewport::new(6, 5);
+        let far = Viewport::centered_on(Coord::new(-300, 40), 6, 5);
+        for viewport in [near, far] {
+            let m = Mask::from_viewport(&viewport, &odd);
+            let expected = Mask::from_predicate(viewport.min(), viewport.max(), &odd);
+            assert_eq!(m, expected);
+        }
+    }
+    #[test]
+    fn check_empty() {
+        let m = Mask::from_fn(Coord::new(1, 1), Coord::new(0, 0), |_| true);
+        assert_eq!(m.width, 0);
+        assert_eq!(m.count(), 0);
+    }
+}
@@ -0,0 +1,292 @@
+/// Renders that remember how they were made: the parameters go into PNG text chunks
+/// and `render_from_metadata` draws the image again from them.
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cramer::CramerModel,
+    palette::{
+        ByDivisorCount, ByPrimeGap, ByQuad, ByResidue, ColourMapper, Highlight, ModSix,
+        ValueGradient,
+    },
+    predicate::{
+        Abundant, AlmostPrimes, Deficient, Fibonacci, NumberPredicate, Palindromes, Perfect,
+        PerfectSquares, Primes, Semiprimes, Triangular,
+    },
+    ulamspiral_img::{render, render_colour},
+    viewport::Viewport,
+};
+
+/// The only way this crate numbers the spiral, written down so images stay readable
+/// if that ever changes.
+pub const CONVENTION: &str = "0 at the center, 1 east of it, turning counter-clockwise";
+
+/// The text chunk holding the whole `RenderSpec` as JSON.
+const SPEC_KEY: &str = "ulam:spec";
+
+/// The built in predicates, by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum PredicateSpec {
+    Primes,
+    PerfectSquares,
+    Triangular,
+    Fibonacci,
+    Abundant,
+    Deficient,
+    Perfect,
+    Semiprimes,
+    AlmostPrimes { k: u32 },
+    Palindromes { base: u64 },
+    Cramer { seed: u64 },
+}
+
+impl PredicateSpec {
+    pub fn predicate(&self) -> Box<dyn NumberPredicate> {
+        match *self {
+            PredicateSpec::Primes => Box::new(Primes::new()),
+            PredicateSpec::PerfectSquares => Box::new(PerfectSquares),
+            PredicateSpec::Triangular => Box::new(Triangular),
+            PredicateSpec::Fibonacci => Box::new(Fibonacci),
+            PredicateSpec::Abundant => Box::new(Abundant),
+            PredicateSpec::Deficient => Box::new(Deficient),
+            PredicateSpec::Perfect => Box::new(Perfect),
+            PredicateSpec::Semiprimes => Box::new(Semiprimes),
+            PredicateSpec::AlmostPrimes { k } => Box::new(AlmostPrimes(k)),
+            PredicateSpec::Palindromes { base } => Box::new(Palindromes(base)),
+            PredicateSpec::Cramer { seed } => Box::new(CramerModel::new(seed)),
+        }
+    }
+}
+
+/// The built in colour mappers with their settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum PaletteSpec {
+    ModSix,
+    ByResidue(ByResidue),
+    ByQuad(ByQuad),
+    ByPrimeGap(ByPrimeGap),
+    ByDivisorCount(ByDivisorCount),
+    ValueGradient(ValueGradient),
+    Highlight { colour: [u8; 4] },
+}
+
+impl PaletteSpec {
+    pub fn mapper(&self) -> Box<dyn ColourMapper> {
+        match *self {
+            PaletteSpec::ModSix => Box::new(ModSix),
+            PaletteSpec::ByResidue(mapper) => Box::new(mapper),
+            PaletteSpec::ByQuad(mapper) => Box::new(mapper),
+            PaletteSpec::ByPrimeGap(mapper) => Box::new(mapper),
+            PaletteSpec::ByDivisorCount(mapper) => Box::new(mapper),
+            PaletteSpec::ValueGradient(mapper) => Box::new(mapper),
+            PaletteSpec::Highlight { colour } => Box::new(Highlight(colour)),
+        }
+    }
+}
+
+/// Everything needed to draw an image again. Without a palette the predicate is drawn
+/// in white on black as `render` does, with one it is coloured as `render_colour` does.
+/// # Examples
+/// ```no_run
+/// use ulam::{metadata::{render_from_metadata, PredicateSpec, RenderSpec}, viewport::Viewport};
+/// let spec = RenderSpec::new(Viewport::new(400, 400).with_scale(2), PredicateSpec::Primes);
+/// spec.save("spiral.png").unwrap();
+/// // later, with nothing but the file
+/// let again = render_from_metadata("spiral.png").unwrap();
+/// // or twice as much of it
+/// let mut bigger = RenderSpec::read("spiral.png").unwrap();
+/// bigger.viewport.width *= 2;
+/// bigger.viewport.height *= 2;
+/// bigger.save("bigger.png").unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderSpec {
+    pub viewport: Viewport,
+    pub predicate: PredicateSpec,
+    pub palette: Option<PaletteSpec>,
+}
+
+impl RenderSpec {
+    pub fn new(viewport: Viewport, predicate: PredicateSpec) -> RenderSpec {
+        RenderSpec {
+            viewport,
+            predicate,
+            palette: None,
+        }
+    }
+
+    pub fn with_palette(self, palette: PaletteSpec) -> RenderSpec {
+        RenderSpec {
+            palette: Some(palette),
+            ..self
+        }
+    }
+
+    pub fn render(&self) -> Result<image::DynamicImage, Box<dyn Error>> {
+        let predicate = self.predicate.predicate();
+        Ok(match &self.palette {
+            None => render(&self.viewport, &*predicate)?.into(),
+            Some(palette) => render_colour(&self.viewport, &*predicate, &*palette.mapper())?.into(),
+        })
+    }
+
+    /// Write `img`, which should be this spec's render, as a PNG with the spec in its text chunks.
+    pub fn write_png<W: Write>(
+        &self,
+        writer: W,
+        img: &image::DynamicImage,
+    ) -> Result<(), Box<dyn Error>> {
+        let (colour, data) = match img {
+            image::DynamicImage::ImageLuma8(img) => (png::ColorType::Grayscale, img.as_raw()),
+            image::DynamicImage::ImageRgb8(img) => (png::ColorType::Rgb, img.as_raw()),
+            _ => return Err("only grey and RGB renders are written".into()),
+        };
+        let mut encoder = png::Encoder::new(writer, img.width(), img.height());
+        encoder.set_color(colour);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Fast);
+        let version = env!("CARGO_PKG_VERSION");
+        // readable one by one with any PNG tool, the JSON is what gets read back.
+        let mut text = vec![
+            ("Software", format!("ulam {version}")),
+            ("ulam:version", version.to_string()),
+            ("ulam:size", format!("{}x{}", img.width(), img.height())),
+            ("ulam:convention", CONVENTION.to_string()),
+            ("ulam:viewport", serde_json::to_string(&self.viewport)?),
+            ("ulam:predicate", name(&self.predicate)?),
+        ];
+        if let Some(palette) = &self.palette {
+            text.push(("ulam:palette", name(palette)?));
+        }
+        for (keyword, value) in text {
+            encoder.add_text_chunk(keyword.to_string(), value)?;
+        }
+        encoder.add_itxt_chunk(SPEC_KEY.to_string(), serde_json::to_string(self)?)?;
+        let mut png = encoder.write_header()?;
+        png.write_image_data(data)?;
+        png.finish()?;
+        Ok(())
+    }
+
+    /// Render and save as a PNG with the spec in its text chunks.
+    pub fn save<Q: AsRef<Path>>(&self, path: Q) -> Result<(), Box<dyn Error>> {
+        let img = self.render()?;
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_png(&mut file, &img)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// The spec saved in a PNG by `save` or `write_png`.
+    pub fn read<Q: AsRef<Path>>(path: Q) -> Result<RenderSpec, Box<dyn Error>> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let reader = decoder.read_info()?;
+        let info = reader.info();
+        if let Some(convention) = info
+            .uncompressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == "ulam:convention")
+        {
+            if convention.text != CONVENTION {
+                return Err(format!("drawn with another numbering, {}", convention.text).into());
+            }
+        }
+        let spec = info
+            .utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == SPEC_KEY)
+            .ok_or("no ulam metadata in this image")?;
+        Ok(serde_json::from_str(&spec.get_text()?)?)
+    }
+}
+
+/// Draw an image again from the spec saved in it.
+pub fn render_from_metadata<Q: AsRef<Path>>(
+    path: Q,
+) -> Result<image::DynamicImage, Box<dyn Error>> {
+    RenderSpec::read(path)?.render()
+}
+
+// The `name` tag of a spec, the rest of its settings are in the JSON.
+fn name<T: Serialize>(spec: &T) -> Result<String, Box<dyn Error>> {
+    let json = serde_json::to_value(spec)?;
+    let name = json["name"].as_str().ok_or("spec without a name")?;
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{palette::Colormap, Coord};
+    fn temp(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ulam-metadata-{name}-{}.png", std::process::id()))
+    }
+    #[test]
+    fn check_round_trip() {
+        let viewport = Viewport::centered_on(Coord::new(30, -12), 60, 40).with_scale(2);
+        let mut residue = ByResidue::new(7);
+        residue.colormap = Colormap::Magma;
+        for spec in [
+            RenderSpec::new(viewport, PredicateSpec::Primes),
+            RenderSpec::new(viewport, PredicateSpec::AlmostPrimes { k: 3 })
+                .with_palette(PaletteSpec::ByResidue(residue)),
+            RenderSpec::new(
+                viewport.with_downsample(4),
+                PredicateSpec::Cramer { seed: 9 },
+            ),
+        ] {
+            let path = temp("round-trip");
+            spec.save(&path).unwrap();
+            assert_eq!(RenderSpec::read(&path).unwrap(), spec);
+            let again = render_from_metadata(&path).unwrap();
+            assert_eq!(again, image::open(&path).unwrap());
+            assert_eq!(again, spec.render().unwrap());
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+    #[test]
+    fn check_text_chunks() {
+        let spec = RenderSpec::new(
+            Viewport::new(20, 10),
+            PredicateSpec::Palindromes { base: 2 },
+        )
+        .with_palette(PaletteSpec::Highlight {
+            colour: [255, 0, 0, 255],
+        });
+        let mut png = Vec::new();
+        spec.write_png(&mut png, &spec.render().unwrap()).unwrap();
+        let reader = png::Decoder::new(std::io::Cursor::new(png))
+            .read_info()
+            .unwrap();
+        let text: Vec<(&str, &str)> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str()))
+            .collect();
+        assert!(text.contains(&("ulam:size", "20x10")));
+        assert!(text.contains(&("ulam:predicate", "palindromes")));
+        assert!(text.contains(&("ulam:palette", "highlight")));
+        assert!(text.contains(&("ulam:convention", CONVENTION)));
+        assert!(text
+            .iter()
+            .any(|(key, value)| *key == "Software" && value.starts_with("ulam ")));
+
+        // a PNG from anywhere else has nothing to read back
+        let path = temp("plain");
+        crate::ulamspiral_img::generate(10, 10)
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        assert!(RenderSpec::read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
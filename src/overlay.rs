@@ -0,0 +1,280 @@
+/// Lines drawn over a rendered spiral: its path, ring and wedge boundaries, the axes and lines of your own.
+use std::error::Error;
+
+use crate::{projection::Projection, viewport::Viewport, Coord};
+
+// x and y, in cells or pixels.
+type Point = (f64, f64);
+
+/// What an overlay draws. Positions are cell centres, so a line between two cells
+/// runs through the middle of both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// The path the values take, from 0 out through every ring in view.
+    SpiralPath,
+    /// The outer edge of every `every`th ring, ring 0 being the cell at the center.
+    Rings {
+        every: u32,
+    },
+    /// The diagonals through the center, where one `Quad` wedge meets the next.
+    Wedges,
+    /// The x and y axes.
+    Axes,
+    Segment {
+        from: Coord,
+        to: Coord,
+    },
+    /// A line through `through` in `direction`, running off both sides of the image.
+    /// # Examples
+    /// ```
+    /// use ulam::{overlay::Layer, Coord};
+    /// // the diagonal of 4n^2 + 4n, going south east from 0
+    /// let line = Layer::Line { through: Coord::new(0, 0), direction: (1, -1) };
+    /// ```
+    Line {
+        through: Coord,
+        direction: (i32, i32),
+    },
+}
+
+/// A layer with its colour and thickness in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlay {
+    pub layer: Layer,
+    pub colour: [u8; 3],
+    pub thickness: u32,
+}
+
+impl Overlay {
+    /// One pixel of white.
+    pub fn new(layer: Layer) -> Overlay {
+        Overlay {
+            layer,
+            colour: [255, 255, 255],
+            thickness: 1,
+        }
+    }
+
+    pub fn with_colour(self, colour: [u8; 3]) -> Overlay {
+        Overlay { colour, ..self }
+    }
+
+    pub fn with_thickness(self, thickness: u32) -> Overlay {
+        Overlay { thickness, ..self }
+    }
+
+    // The line segments of the layer in cell coordinates, y going up.
+    fn segments(&self, viewport: &Viewport) -> Result<Vec<[Point; 2]>, Box<dyn Error>> {
+        let (min, max) = (viewport.min(), viewport.max());
+        // the outermost ring in view.
+        let rings = [min.x, min.y, max.x, max.y]
+            .iter()
+            .map(|v| f64::from(v.unsigned_abs()))
+            .fold(0.0, f64::max);
+        let far = rings + 1.0;
+        let point = |c: &Coord| (f64::from(c.x), f64::from(c.y));
+        Ok(match self.layer {
+            Layer::SpiralPath => {
+                let mut corners = vec![(0.0, 0.0)];
+                for k in 1..=rings as i64 + 1 {
+                    let k = k as f64;
+                    corners.extend([(k, 1.0 - k), (k, k), (-k, k), (-k, -k), (k, -k)]);
+                }
+                corners.windows(2).map(|w| [w[0], w[1]]).collect()
+            }
+            Layer::Rings { every } => (0..=rings as u32)
+                .filter(|k| k % every.max(1) == 0)
+                .flat_map(|k| {
+                    let e = f64::from(k) + 0.5;
+                    [
+                        [(-e, e), (e, e)],
+                        [(e, e), (e, -e)],
+                        [(e, -e), (-e, -e)],
+                        [(-e, -e), (-e, e)],
+                    ]
+                })
+                .collect(),
+            Layer::Wedges => vec![[(-far, -far), (far, far)], [(-far, far), (far, -far)]],
+            Layer::Axes => vec![[(-far, 0.0), (far, 0.0)], [(0.0, -far), (0.0, far)]],
+            Layer::Segment { from, to } => vec![[point(&from), point(&to)]],
+            Layer::Line { through, direction } => {
+                if direction == (0, 0) {
+                    return Err("a line needs a direction".into());
+                }
+                let (x, y) = point(&through);
+                // far enough along to be out of view either way.
+                let t = far + x.abs() + y.abs();
+                let (dx, dy) = (f64::from(direction.0), f64::from(direction.1));
+                vec![[(x - dx * t, y - dy * t), (x + dx * t, y + dy * t)]]
+            }
+        })
+    }
+}
+
+/// Draw the overlays in order over `img`, a render of `viewport` such as from `render_colour`
+/// or `generate_colour` with `Viewport::new`.
+/// # Examples
+/// ```
+/// use ulam::{overlay::{draw, Layer, Overlay}, ulamspiral_img::generate_colour, viewport::Viewport};
+/// let mut img = generate_colour(200, 200).unwrap();
+/// let overlays = [
+///     Overlay::new(Layer::SpiralPath).with_colour([90, 90, 90]),
+///     Overlay::new(Layer::Axes).with_colour([255, 0, 0]).with_thickness(2),
+/// ];
+/// draw(&mut img, &Viewport::new(200, 200), &overlays).unwrap();
+/// ```
+pub fn draw(
+    img: &mut image::RgbImage,
+    viewport: &Viewport,
+    overlays: &[Overlay],
+) -> Result<(), Box<dyn Error>> {
+    if img.dimensions() != (viewport.pixel_width()?, viewport.pixel_height()?) {
+        return Err("image is not the size of the viewport".into());
+    }
+    let projection = Projection::new(viewport);
+    let to_pixel = |(x, y): Point| projection.to_pixel(x, y);
+    for overlay in overlays {
+        let colour = image::Rgb(overlay.colour);
+        for [a, b] in overlay.segments(viewport)? {
+            line(img, to_pixel(a), to_pixel(b), overlay.thickness, colour);
+        }
+    }
+    Ok(())
+}
+
+// Draw a line between two pixel positions by stamping squares `thickness` across along it.
+fn line(img: &mut image::RgbImage, a: Point, b: Point, thickness: u32, colour: image::Rgb<u8>) {
+    let t = f64::from(thickness);
+    let bounds = (
+        -t,
+        -t,
+        f64::from(img.width()) + t,
+        f64::from(img.height()) + t,
+    );
+    let Some((a, b)) = clip(a, b, bounds) else {
+        return;
+    };
+    let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0) as u32;
+    for i in 0..=steps {
+        let f = f64::from(i) / f64::from(steps);
+        let (x, y) = (a.0 + (b.0 - a.0) * f, a.1 + (b.1 - a.1) * f);
+        let corner = |v: f64| (v - t / 2.0 + 0.5).floor() as i64;
+        let (left, top) = (corner(x), corner(y));
+        for py in top.max(0)..(top + i64::from(thickness)).min(i64::from(img.height())) {
+            for px in left.max(0)..(left + i64::from(thickness)).min(i64::from(img.width())) {
+                img.put_pixel(px as u32, py as u32, colour);
+            }
+        }
+    }
+}
+
+// The part of the segment from `a` to `b` inside (min x, min y, max x, max y), if any.
+fn clip(a: Point, b: Point, (x0, y0, x1, y1): (f64, f64, f64, f64)) -> Option<(Point, Point)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut enter, mut exit) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-dx, a.0 - x0),
+        (dx, x1 - a.0),
+        (-dy, a.1 - y0),
+        (dy, y1 - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            enter = enter.max(q / p);
+        } else {
+            exit = exit.min(q / p);
+        }
+    }
+    (enter <= exit).then_some((
+        (a.0 + dx * enter, a.1 + dy * enter),
+        (a.0 + dx * exit, a.1 + dy * exit),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const RED: [u8; 3] = [255, 0, 0];
+    fn blank(viewport: &Viewport) -> image::RgbImage {
+        image::RgbImage::new(
+            viewport.pixel_width().unwrap(),
+            viewport.pixel_height().unwrap(),
+        )
+    }
+    fn red(img: &image::RgbImage) -> Vec<(u32, u32)> {
+        img.enumerate_pixels()
+            .filter(|(_, _, p)| p.0 == RED)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+    #[test]
+    fn check_axes_and_wedges() {
+        let viewport = Viewport::new(9, 9);
+        let mut img = blank(&viewport);
+        let axes = Overlay::new(Layer::Axes).with_colour(RED);
+        draw(&mut img, &viewport, &[axes]).unwrap();
+        let marked = red(&img);
+        assert_eq!(marked.len(), 9 + 9 - 1);
+        assert!(marked.iter().all(|(x, y)| *x == 4 || *y == 4));
+
+        let mut img = blank(&viewport);
+        draw(
+            &mut img,
+            &viewport,
+            &[Overlay::new(Layer::Wedges).with_colour(RED)],
+        )
+        .unwrap();
+        let marked = red(&img);
+        assert_eq!(marked.len(), 9 + 9 - 1);
+        assert!(marked.iter().all(|(x, y)| x == y || x + y == 8));
+    }
+    #[test]
+    fn check_spiral_path() {
+        // at 4 pixels a cell the path from 0 to 1 goes right and 1 to 2 goes up
+        let viewport = Viewport::new(3, 3).with_scale(4);
+        let mut img = blank(&viewport);
+        let path = Overlay::new(Layer::SpiralPath).with_colour(RED);
+        draw(&mut img, &viewport, &[path]).unwrap();
+        assert_eq!(img.get_pixel(8, 6).0, RED);
+        assert_eq!(img.get_pixel(10, 4).0, RED);
+        // nothing goes from 0 to 3 or 0 to 7
+        assert_ne!(img.get_pixel(4, 4).0, RED);
+        assert_ne!(img.get_pixel(6, 8).0, RED);
+    }
+    #[test]
+    fn check_rings_and_lines() {
+        let viewport = Viewport::new(5, 5).with_scale(2);
+        let mut img = blank(&viewport);
+        let rings = Overlay::new(Layer::Rings { every: 1 }).with_colour(RED);
+        draw(&mut img, &viewport, &[rings]).unwrap();
+        // the sides of ring 0 are at pixels 4 and 6, ring 1 at 2 and 8
+        assert_eq!(img.get_pixel(4, 5).0, RED);
+        assert_eq!(img.get_pixel(8, 5).0, RED);
+        assert_ne!(img.get_pixel(7, 5).0, RED);
+
+        let mut img = blank(&viewport);
+        let far = Coord::new(1_000_000, 0);
+        let line = Layer::Line {
+            through: far,
+            direction: (1, 1),
+        };
+        draw(&mut img, &viewport, &[Overlay::new(line).with_colour(RED)]).unwrap();
+        assert!(red(&img).is_empty());
+        let segment = Layer::Segment {
+            from: Coord::new(-2, 2),
+            to: Coord::new(2, 2),
+        };
+        let thick = Overlay::new(segment).with_colour(RED).with_thickness(2);
+        draw(&mut img, &viewport, &[thick]).unwrap();
+        assert_eq!(red(&img).len(), 2 * 10);
+        let bad = Layer::Line {
+            through: far,
+            direction: (0, 0),
+        };
+        assert!(draw(&mut img, &viewport, &[Overlay::new(bad)]).is_err());
+        assert!(draw(&mut blank(&Viewport::new(4, 4)), &viewport, &[]).is_err());
+    }
+}
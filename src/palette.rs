@@ -0,0 +1,447 @@
+/// Colouring cells of the spiral, from a single cell's value, place and highlight to an RGBA colour.
+use serde::{Deserialize, Serialize};
+
+use crate::{predicate::divisor_count, quad_of_coord, Coord, Quad};
+
+/// Everything a `ColourMapper` gets to know about a cell.
+/// `highlighted` is whether the predicate being drawn (usually the primes) picked it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub coord: Coord,
+    pub value: u64,
+    pub quad: Quad,
+    pub highlighted: bool,
+}
+
+impl Cell {
+    pub fn new(coord: Coord, value: u64, highlighted: bool) -> Cell {
+        Cell {
+            coord,
+            value,
+            quad: quad_of_coord(&coord),
+            highlighted,
+        }
+    }
+}
+
+/// Picks the RGBA colour of each cell, an alpha of 0 leaves the background showing.
+/// Any closure taking a `&Cell` and returning `[u8; 4]` is also a `ColourMapper`.
+pub trait ColourMapper {
+    fn colour(&self, cell: &Cell) -> [u8; 4];
+}
+
+impl<F: Fn(&Cell) -> [u8; 4]> ColourMapper for F {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        self(cell)
+    }
+}
+
+pub const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+/// Perceptually uniform colormaps. `Cividis` and `OkabeIto` stay readable with colour blindness,
+/// `OkabeIto` is a set of 8 distinct colours for categories rather than a gradient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Colormap {
+    Viridis,
+    Magma,
+    Cividis,
+    OkabeIto,
+}
+
+// evenly spaced, every ninth of the way.
+const VIRIDIS: [[u8; 3]; 10] = [
+    [68, 1, 84],
+    [72, 40, 120],
+    [62, 73, 137],
+    [49, 104, 142],
+    [38, 130, 142],
+    [31, 158, 137],
+    [53, 183, 121],
+    [110, 206, 88],
+    [181, 222, 43],
+    [253, 231, 37],
+];
+
+const MAGMA: [[u8; 3]; 9] = [
+    [0, 0, 4],
+    [28, 16, 68],
+    [79, 18, 123],
+    [129, 37, 129],
+    [181, 54, 122],
+    [229, 80, 100],
+    [251, 135, 97],
+    [254, 194, 135],
+    [252, 253, 191],
+];
+
+const CIVIDIS: [[u8; 3]; 9] = [
+    [0, 32, 76],
+    [0, 51, 111],
+    [57, 72, 107],
+    [87, 92, 109],
+    [115, 113, 114],
+    [143, 135, 121],
+    [174, 158, 115],
+    [207, 182, 102],
+    [255, 234, 70],
+];
+
+// black is left out so nothing disappears into the background.
+const OKABE_ITO: [[u8; 3]; 8] = [
+    [230, 159, 0],
+    [86, 180, 233],
+    [0, 158, 115],
+    [240, 228, 66],
+    [0, 114, 178],
+    [213, 94, 0],
+    [204, 121, 167],
+    [153, 153, 153],
+];
+
+impl Colormap {
+    /// The colour at `t`, from 0 to 1.
+    pub fn sample(&self, t: f64) -> [u8; 3] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let stops: &[[u8; 3]] = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Magma => &MAGMA,
+            Colormap::Cividis => &CIVIDIS,
+            Colormap::OkabeIto => {
+                let i = (t * (OKABE_ITO.len() - 1) as f64).round() as usize;
+                return OKABE_ITO[i];
+            }
+        };
+        let pos = t * (stops.len() - 1) as f64;
+        let i = (pos.floor() as usize).min(stops.len() - 2);
+        let f = pos - i as f64;
+        let mix = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * f).round() as u8;
+        [
+            mix(stops[i][0], stops[i + 1][0]),
+            mix(stops[i][1], stops[i + 1][1]),
+            mix(stops[i][2], stops[i + 1][2]),
+        ]
+    }
+
+    /// The colour for category `i` out of `count`.
+    pub fn category(&self, i: u64, count: u64) -> [u8; 3] {
+        match self {
+            Colormap::OkabeIto => OKABE_ITO[(i % OKABE_ITO.len() as u64) as usize],
+            _ if count <= 1 => self.sample(0.0),
+            _ => self.sample(i as f64 / (count - 1) as f64),
+        }
+    }
+}
+
+// A mapper colour blended by its alpha over an opaque background.
+pub(crate) fn over([r, g, b, a]: [u8; 4], background: [u8; 3]) -> [u8; 3] {
+    let mix = |c: u8, bg: u8| {
+        ((u16::from(c) * u16::from(a) + u16::from(bg) * u16::from(255 - a)) / 255) as u8
+    };
+    [
+        mix(r, background[0]),
+        mix(g, background[1]),
+        mix(b, background[2]),
+    ]
+}
+
+fn opaque(rgb: [u8; 3]) -> [u8; 4] {
+    [rgb[0], rgb[1], rgb[2], 255]
+}
+
+/// Colour every highlighted cell the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Highlight(pub [u8; 4]);
+
+impl ColourMapper for Highlight {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if cell.highlighted {
+            self.0
+        } else {
+            TRANSPARENT
+        }
+    }
+}
+
+/// Colour by `value % modulus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByResidue {
+    pub modulus: u64,
+    pub colormap: Colormap,
+    /// Colour the cells that are not highlighted too.
+    pub all_cells: bool,
+}
+
+impl ByResidue {
+    pub fn new(modulus: u64) -> ByResidue {
+        ByResidue {
+            modulus,
+            colormap: Colormap::OkabeIto,
+            all_cells: false,
+        }
+    }
+}
+
+impl ColourMapper for ByResidue {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if !(cell.highlighted || self.all_cells) {
+            return TRANSPARENT;
+        }
+        let modulus = self.modulus.max(1);
+        opaque(self.colormap.category(cell.value % modulus, modulus))
+    }
+}
+
+/// Colour by which `Quad` the cell sits in. The eight wedges take eight colours of the
+/// colormap and the center is white, apart from all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByQuad {
+    pub colormap: Colormap,
+    pub all_cells: bool,
+}
+
+impl ByQuad {
+    pub fn new() -> ByQuad {
+        ByQuad {
+            colormap: Colormap::OkabeIto,
+            all_cells: false,
+        }
+    }
+}
+
+impl Default for ByQuad {
+    fn default() -> ByQuad {
+        ByQuad::new()
+    }
+}
+
+impl ColourMapper for ByQuad {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if !(cell.highlighted || self.all_cells) {
+            return TRANSPARENT;
+        }
+        let i = match cell.quad {
+            Quad::North => 0,
+            Quad::NorthEast => 1,
+            Quad::East => 2,
+            Quad::SouthEast => 3,
+            Quad::South => 4,
+            Quad::SouthWest => 5,
+            Quad::West => 6,
+            Quad::NorthWest => 7,
+            Quad::Center => return [255; 4],
+        };
+        opaque(self.colormap.category(i, 8))
+    }
+}
+
+/// Colour by the distance to the next prime, which for a prime is its prime gap.
+/// Gaps of `max_gap` or more get the top of the colormap.
+#[cfg(feature = "prime")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByPrimeGap {
+    pub max_gap: u64,
+    pub colormap: Colormap,
+    pub all_cells: bool,
+}
+
+#[cfg(feature = "prime")]
+impl ByPrimeGap {
+    pub fn new(max_gap: u64) -> ByPrimeGap {
+        ByPrimeGap {
+            max_gap,
+            colormap: Colormap::Viridis,
+            all_cells: false,
+        }
+    }
+}
+
+#[cfg(feature = "prime")]
+impl ColourMapper for ByPrimeGap {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if !(cell.highlighted || self.all_cells) {
+            return TRANSPARENT;
+        }
+        let gap = (cell.value + 1..)
+            .find(|n| primal::is_prime(*n))
+            .map_or(0, |next| next - cell.value);
+        opaque(
+            self.colormap
+                .sample(gap as f64 / self.max_gap.max(1) as f64),
+        )
+    }
+}
+
+/// Colour by how many divisors the value has, `max` or more gets the top of the colormap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByDivisorCount {
+    pub max: u64,
+    pub colormap: Colormap,
+    pub all_cells: bool,
+}
+
+impl ByDivisorCount {
+    pub fn new(max: u64) -> ByDivisorCount {
+        ByDivisorCount {
+            max,
+            colormap: Colormap::Magma,
+            all_cells: true,
+        }
+    }
+}
+
+impl ColourMapper for ByDivisorCount {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if !(cell.highlighted || self.all_cells) {
+            return TRANSPARENT;
+        }
+        let count = divisor_count(cell.value);
+        opaque(self.colormap.sample(count as f64 / self.max.max(1) as f64))
+    }
+}
+
+/// Colour by the value itself, from 0 up to `max_value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueGradient {
+    pub max_value: u64,
+    pub colormap: Colormap,
+    pub all_cells: bool,
+}
+
+impl ValueGradient {
+    pub fn new(max_value: u64) -> ValueGradient {
+        ValueGradient {
+            max_value,
+            colormap: Colormap::Viridis,
+            all_cells: false,
+        }
+    }
+}
+
+impl ColourMapper for ValueGradient {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if !(cell.highlighted || self.all_cells) {
+            return TRANSPARENT;
+        }
+        opaque(
+            self.colormap
+                .sample(cell.value as f64 / self.max_value.max(1) as f64),
+        )
+    }
+}
+
+/// The colours `ulamspiral_img::generate_colour` has always used, by value mod 6.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModSix;
+
+impl ColourMapper for ModSix {
+    fn colour(&self, cell: &Cell) -> [u8; 4] {
+        if !cell.highlighted {
+            return TRANSPARENT;
+        }
+        opaque(match cell.value % 6 {
+            0 => [255, 0, 0],
+            1 => [0, 255, 0],
+            3 => [255, 0, 0],
+            5 => [0, 0, 255],
+            _ => [255, 255, 255],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn cell(value: u64, highlighted: bool) -> Cell {
+        Cell::new(crate::calc_coord::calc_coord_u64(value), value, highlighted)
+    }
+    #[test]
+    fn check_colormaps() {
+        assert_eq!(Colormap::Viridis.sample(0.0), [68, 1, 84]);
+        assert_eq!(Colormap::Viridis.sample(1.0), [253, 231, 37]);
+        assert_eq!(Colormap::Viridis.sample(8.0 / 9.0), [181, 222, 43]);
+        // halfway is #21918c, up to rounding between the stops either side
+        let middle = Colormap::Viridis.sample(0.5);
+        for (got, want) in middle.iter().zip([0x21, 0x91, 0x8c]) {
+            assert!(got.abs_diff(want) <= 2, "{middle:?}");
+        }
+        assert_eq!(Colormap::Magma.sample(0.5), [181, 54, 122]);
+        assert_eq!(Colormap::Magma.sample(0.0625), [14, 8, 36]);
+        assert_eq!(Colormap::Cividis.sample(2.0), Colormap::Cividis.sample(1.0));
+        assert_eq!(Colormap::OkabeIto.category(9, 3), OKABE_ITO[1]);
+        assert_eq!(
+            Colormap::Viridis.category(2, 3),
+            Colormap::Viridis.sample(1.0)
+        );
+    }
+    #[test]
+    fn check_residue() {
+        let by = ByResidue::new(6);
+        assert_eq!(by.colour(&cell(7, true)), by.colour(&cell(13, true)));
+        assert_ne!(by.colour(&cell(7, true)), by.colour(&cell(11, true)));
+        assert_eq!(by.colour(&cell(8, false)), TRANSPARENT);
+        let all = ByResidue {
+            all_cells: true,
+            ..by
+        };
+        assert_eq!(all.colour(&cell(8, false))[3], 255);
+    }
+    #[test]
+    fn check_quad() {
+        let by = ByQuad::new();
+        // 3 and 14 are both straight north
+        assert_eq!(by.colour(&cell(3, true)), by.colour(&cell(14, true)));
+        assert_ne!(by.colour(&cell(3, true)), by.colour(&cell(5, true)));
+        // the center stands apart from every wedge, with any colormap
+        for colormap in [Colormap::OkabeIto, Colormap::Viridis] {
+            let by = ByQuad {
+                colormap,
+                all_cells: true,
+            };
+            let center = by.colour(&cell(0, false));
+            let wedges: Vec<[u8; 4]> = (1..=8).map(|v| by.colour(&cell(v, false))).collect();
+            assert!(!wedges.contains(&center), "{colormap:?}");
+            let mut distinct = wedges.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            assert_eq!(distinct.len(), 8, "{colormap:?}");
+        }
+    }
+    #[cfg(feature = "prime")]
+    #[test]
+    fn check_gap() {
+        let by = ByPrimeGap::new(6);
+        // 23 to 29 is the first gap of 6
+        assert_eq!(
+            by.colour(&cell(23, true)),
+            opaque(Colormap::Viridis.sample(1.0))
+        );
+        assert_eq!(
+            by.colour(&cell(3, true)),
+            opaque(Colormap::Viridis.sample(2.0 / 6.0))
+        );
+    }
+    #[test]
+    fn check_divisors_and_gradient() {
+        let by = ByDivisorCount::new(6);
+        assert_eq!(
+            by.colour(&cell(12, false)),
+            opaque(Colormap::Magma.sample(1.0))
+        );
+        let by = ValueGradient::new(100);
+        assert_eq!(
+            by.colour(&cell(50, true)),
+            opaque(Colormap::Viridis.sample(0.5))
+        );
+    }
+    #[test]
+    fn check_closure() {
+        let white = |c: &Cell| if c.value > 10 { [255; 4] } else { TRANSPARENT };
+        assert_eq!(white.colour(&cell(11, false)), [255; 4]);
+    }
+    #[test]
+    fn check_over() {
+        assert_eq!(over([200, 100, 0, 255], [9, 9, 9]), [200, 100, 0]);
+        assert_eq!(over([200, 100, 0, 0], [9, 9, 9]), [9, 9, 9]);
+        assert_eq!(over([255, 0, 51, 128], [0; 3]), [128, 0, 25]);
+    }
+}
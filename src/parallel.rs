@@ -0,0 +1,270 @@
+/// Multi-threaded versions of the `ulamspiral_img` renderers, drawing the image in bands of rows
+/// on the rayon thread pool. They give the same images as the single-threaded ones.
+use std::error::Error;
+
+use rayon::prelude::*;
+
+use crate::{
+    palette::{over, Cell, ColourMapper},
+    predicate::{NumberPredicate, Primes},
+    ulamspiral_img::{self, canvas, GreyImage},
+    value_of_coord_u64,
+    viewport::Viewport,
+    Coord,
+};
+
+/// Fewest cell rows drawn by one task.
+const BAND: u32 = 16;
+/// Values sieved by one task, a multiple of 64 so tasks never share a word.
+const SEGMENT: u64 = 1 << 18;
+
+/// Same as `ulamspiral_img::generate`.
+pub fn generate(x_size: u32, y_size: u32) -> Result<GreyImage, Box<dyn Error>> {
+    let viewport = Viewport::new(x_size, y_size);
+    render(&viewport, &Primes::up_to(viewport.max_value()))
+}
+
+pub fn generate_colour(x_size: u32, y_size: u32) -> Result<image::RgbImage, Box<dyn Error>> {
+    let viewport = Viewport::new(x_size, y_size);
+    render_colour(
+        &viewport,
+        &Primes::up_to(viewport.max_value()),
+        &crate::palette::ModSix,
+    )
+}
+
+/// Same as `ulamspiral_img::render`. The members of the predicate are listed one segment
+/// of values per task, so `Primes::new()` sieves its segments in parallel.
+/// Downsampled viewports are passed on to the single-threaded `render_density`.
+/// # Examples
+/// ```
+/// use ulam::{parallel, predicate::Primes, ulamspiral_img, viewport::Viewport};
+/// let viewport = Viewport::new(300, 200).with_scale(2);
+/// let img = parallel::render(&viewport, &Primes::new()).unwrap();
+/// assert_eq!(img, ulamspiral_img::render(&viewport, &Primes::new()).unwrap());
+/// ```
+pub fn render<P>(viewport: &Viewport, predicate: &P) -> Result<GreyImage, Box<dyn Error>>
+where
+    P: NumberPredicate + Sync + ?Sized,
+{
+    if viewport.downsample > 1 {
+        return ulamspiral_img::render(viewport, predicate);
+    }
+    let mut img: GreyImage = canvas(viewport)?;
+    let marks = Marks::new(viewport, predicate);
+    let scale = viewport.scale as usize;
+    let row_len = viewport.width as usize * scale;
+    paint_bands(&mut img, viewport, [64], |rows, buf| {
+        marks.band(viewport, rows, |row, col| {
+            for y in row * scale..(row + 1) * scale {
+                buf[y * row_len + col * scale..][..scale].fill(255);
+            }
+        });
+    });
+    Ok(img)
+}
+
+/// Same as `ulamspiral_img::render_colour`, the mapper is called from many threads at once.
+pub fn render_colour<P, M>(
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+) -> Result<image::RgbImage, Box<dyn Error>>
+where
+    P: NumberPredicate + Sync + ?Sized,
+    M: ColourMapper + Sync + ?Sized,
+{
+    if viewport.downsample > 1 {
+        return Err("colour renders are not downsampled, use render_density".into());
+    }
+    let mut img: image::RgbImage = canvas(viewport)?;
+    let marks = Marks::new(viewport, predicate);
+    let scale = viewport.scale as usize;
+    let (width, row_len) = (viewport.width as usize, viewport.width as usize * scale * 3);
+    paint_bands(&mut img, viewport, [64, 64, 64], |rows, buf| {
+        let mut marked = vec![false; width * rows.len()];
+        marks.band(viewport, rows.clone(), |row, col| {
+            marked[row * width + col] = true
+        });
+        for (i, &highlighted) in marked.iter().enumerate() {
+            let (row, col) = (i / width, i % width);
+            let coord = viewport.coord_at(col as u32, rows.start + row as u32);
+            let cell = Cell::new(coord, value_of_coord_u64(&coord), highlighted);
+            let colour = mapper.colour(&cell);
+            if colour[3] == 0 {
+                continue;
+            }
+            let pixel = over(colour, [0; 3]);
+            for y in row * scale..(row + 1) * scale {
+                for x in col * scale..(col + 1) * scale {
+                    buf[y * row_len + x * 3..][..3].copy_from_slice(&pixel);
+                }
+            }
+        }
+    });
+    Ok(img)
+}
+
+// Where to look up which cells are in the predicate.
+enum Marks<'a, P: ?Sized> {
+    // every value up to the largest in view, one bit each, for views near 0.
+    Bits(Vec<u64>),
+    // asked cell by cell, for views far out where a bitmap would mostly go unused.
+    Predicate(&'a P),
+}
+
+impl<'a, P: NumberPredicate + Sync + ?Sized> Marks<'a, P> {
+    fn new(viewport: &Viewport, predicate: &'a P) -> Marks<'a, P> {
+        if !viewport.lists_members() {
+            return Marks::Predicate(predicate);
+        }
+        let max = viewport.max_value();
+        let mut bits = vec![0u64; (max / 64 + 1) as usize];
+        bits.par_chunks_mut((SEGMENT / 64) as usize)
+            .enumerate()
+            .for_each(|(i, words)| {
+                let start = i as u64 * SEGMENT;
+                let end = max.min(start + words.len() as u64 * 64 - 1);
+                for value in predicate.members(start, end) {
+                    let bit = value - start;
+                    words[(bit / 64) as usize] |= 1 << (bit % 64);
+                }
+            });
+        Marks::Bits(bits)
+    }
+
+    // Call `mark` with the (row in the band, col) of every marked cell in `rows`.
+    fn band<F: FnMut(usize, usize)>(
+        &self,
+        viewport: &Viewport,
+        rows: std::ops::Range<u32>,
+        mut mark: F,
+    ) {
+        match self {
+            Marks::Predicate(predicate) => {
+                for (i, row) in rows.enumerate() {
+                    for col in 0..viewport.width {
+                        let value = value_of_coord_u64(&viewport.coord_at(col, row));
+                        if predicate.contains(value) {
+                            mark(i, col as usize);
+                        }
+                    }
+                }
+            }
+            Marks::Bits(bits) => {
+                let top_left = viewport.coord_at(0, rows.start);
+                for (start, step, len) in viewport.arms(rows) {
+                    let first = value_of_coord_u64(&start);
+                    for value in set_bits(bits, first, first + len - 1) {
+                        let along = (value - first) as i32;
+                        let c = Coord::new(start.x + step.0 * along, start.y + step.1 * along);
+                        mark((top_left.y - c.y) as usize, (c.x - top_left.x) as usize);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The set bits from `start` to `end` inclusive.
+fn set_bits(bits: &[u64], start: u64, end: u64) -> impl Iterator<Item = u64> + '_ {
+    (start / 64..=end / 64).flat_map(move |w| {
+        let mut word = bits[w as usize];
+        let base = w * 64;
+        if base < start {
+            word &= u64::MAX << (start - base);
+        }
+        if end < base + 63 {
+            word &= u64::MAX >> (base + 63 - end);
+        }
+        std::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let bit = word.trailing_zeros();
+            word &= word - 1;
+            Some(base + u64::from(bit))
+        })
+    })
+}
+
+// Split the image into bands of cell rows and paint each band on its own thread,
+// `paint` gets the rows of a band and the pixels they cover.
+fn paint_bands<Px, const C: usize, F>(
+    img: &mut image::ImageBuffer<Px, Vec<u8>>,
+    viewport: &Viewport,
+    grid: [u8; C],
+    paint: F,
+) where
+    Px: image::Pixel<Subpixel = u8>,
+    F: Fn(std::ops::Range<u32>, &mut [u8]) + Sync,
+{
+    let scale = viewport.scale as usize;
+    let row_len = viewport.width as usize * scale * C;
+    if row_len == 0 {
+        return;
+    }
+    // a few bands per thread keeps them all busy, while bigger bands cut through fewer
+    // arms of the spiral.
+    let band_rows = viewport
+        .height
+        .div_ceil(rayon::current_num_threads() as u32 * 4)
+        .max(BAND);
+    img.par_chunks_mut(row_len * scale * band_rows as usize)
+        .enumerate()
+        .for_each(|(band, buf)| {
+            let first = band as u32 * band_rows;
+            paint(first..(first + band_rows).min(viewport.height), buf);
+            if viewport.grid_lines {
+                let top = first * scale as u32;
+                for (y, line) in buf.chunks_mut(row_len).enumerate() {
+                    for (x, pixel) in line.chunks_mut(C).enumerate() {
+                        if viewport.on_grid_line(x as u32, top + y as u32) {
+                            pixel.copy_from_slice(&grid);
+                        }
+                    }
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::ByQuad;
+    #[test]
+    fn check_set_bits() {
+        let bits = [u64::MAX, 0b1010];
+        assert_eq!(
+            set_bits(&bits, 62, 66).collect::<Vec<_>>(),
+            vec![62, 63, 65]
+        );
+        assert_eq!(set_bits(&bits, 3, 3).count(), 1);
+        assert_eq!(set_bits(&bits, 64, 64).count(), 0);
+    }
+    #[test]
+    fn check_same_as_serial() {
+        let primes = Primes::new();
+        for viewport in [
+            Viewport::new(100, 100),
+            Viewport::new(37, 61).with_scale(3).with_grid_lines(true),
+            Viewport::centered_on(Coord::new(-40, 25), 90, 50),
+            Viewport::centered_on(Coord::new(100_000, 3), 50, 40),
+            Viewport::new(300, 300).with_downsample(7),
+        ] {
+            assert_eq!(
+                render(&viewport, &primes).unwrap(),
+                ulamspiral_img::render(&viewport, &primes).unwrap()
+            );
+        }
+        let viewport = Viewport::centered_on(Coord::new(10, -10), 70, 45).with_scale(2);
+        assert_eq!(
+            render_colour(&viewport, &primes, &ByQuad::new()).unwrap(),
+            ulamspiral_img::render_colour(&viewport, &primes, &ByQuad::new()).unwrap()
+        );
+        assert_eq!(
+            generate_colour(64, 64).unwrap(),
+            ulamspiral_img::generate_colour(64, 64).unwrap()
+        );
+    }
+}
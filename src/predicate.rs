@@ -0,0 +1,362 @@
+/// Sets of numbers that can be highlighted on the spiral in place of the primes.
+///
+/// Any closure taking a `u64` and returning a `bool` is also a `NumberPredicate`.
+pub trait NumberPredicate {
+    fn contains(&self, n: u64) -> bool;
+
+    /// Every member from `start` up to and including `end` in increasing order.
+    /// Worth overriding when the members can be listed faster than testing every number.
+    fn members(&self, start: u64, end: u64) -> Box<dyn Iterator<Item = u64> + '_> {
+        Box::new((start..=end).filter(move |n| self.contains(*n)))
+    }
+
+    /// For listing the members of many ranges of values, none past `max`: the lister passes
+    /// every member from its `start` up to and including its `end` to `found`, in increasing order.
+    /// Worth overriding when work can be shared between the ranges.
+    fn range_lister(&self, max: u64) -> RangeLister<'_> {
+        let _ = max;
+        Box::new(move |start, end, found| {
+            for n in self.members(start, end) {
+                found(n);
+            }
+        })
+    }
+}
+
+/// Made by `NumberPredicate::range_lister`, called with `(start, end, found)`.
+pub type RangeLister<'a> = Box<dyn FnMut(u64, u64, &mut dyn FnMut(u64)) + 'a>;
+
+impl<F: Fn(u64) -> bool> NumberPredicate for F {
+    fn contains(&self, n: u64) -> bool {
+        self(n)
+    }
+}
+
+impl NumberPredicate for crate::cramer::CramerModel {
+    fn contains(&self, n: u64) -> bool {
+        self.is_pseudo_prime(n)
+    }
+}
+
+/// The primes, looked up in a sieve when one was made big enough and tested directly otherwise.
+#[cfg(feature = "prime")]
+#[derive(Debug, Default)]
+pub struct Primes {
+    sieve: Option<primal::Sieve>,
+}
+
+#[cfg(feature = "prime")]
+impl Primes {
+    pub fn new() -> Primes {
+        Primes { sieve: None }
+    }
+
+    /// Sieve every prime up to `limit` ahead of time, worth it when most values below it get asked about.
+    pub fn up_to(limit: u64) -> Primes {
+        Primes {
+            sieve: Some(primal::Sieve::new(limit as usize)),
+        }
+    }
+}
+
+#[cfg(feature = "prime")]
+impl NumberPredicate for Primes {
+    fn contains(&self, n: u64) -> bool {
+        match &self.sieve {
+            Some(sieve) if n as usize <= sieve.upper_bound() => sieve.is_prime(n as usize),
+            _ => primal::is_prime(n),
+        }
+    }
+
+    fn members(&self, start: u64, end: u64) -> Box<dyn Iterator<Item = u64> + '_> {
+        match &self.sieve {
+            Some(sieve) if end as usize <= sieve.upper_bound() => Box::new(
+                sieve
+                    .primes_from(start as usize)
+                    .map(|p| p as u64)
+                    .take_while(move |p| *p <= end),
+            ),
+            _ => Box::new(crate::sieve::SegmentedPrimes::new(start, end)),
+        }
+    }
+
+    /// Sieves every range with the same primes up to the square root of `max`.
+    fn range_lister(&self, max: u64) -> RangeLister<'_> {
+        if let Some(sieve) = self
+            .sieve
+            .as_ref()
+            .filter(|s| max as usize <= s.upper_bound())
+        {
+            return Box::new(move |start, end, found| {
+                for p in sieve.primes_from(start as usize) {
+                    if p as u64 > end {
+                        break;
+                    }
+                    found(p as u64);
+                }
+            });
+        }
+        let base = crate::sieve::BasePrimes::new(max);
+        let mut scratch = Vec::new();
+        Box::new(move |start, end, found| base.primes_in(start, end, &mut scratch, found))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerfectSquares;
+
+impl NumberPredicate for PerfectSquares {
+    fn contains(&self, n: u64) -> bool {
+        is_square(u128::from(n))
+    }
+}
+
+/// 0, 1, 3, 6, 10, ...
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Triangular;
+
+impl NumberPredicate for Triangular {
+    fn contains(&self, n: u64) -> bool {
+        is_square(8 * u128::from(n) + 1)
+    }
+}
+
+/// 0, 1, 2, 3, 5, 8, ...
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fibonacci;
+
+impl NumberPredicate for Fibonacci {
+    fn contains(&self, n: u64) -> bool {
+        let (mut a, mut b) = (0_u128, 1_u128);
+        while a < u128::from(n) {
+            (a, b) = (b, a + b);
+        }
+        a == u128::from(n)
+    }
+}
+
+/// Numbers whose proper divisors add up to more than the number.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Abundant;
+
+impl NumberPredicate for Abundant {
+    fn contains(&self, n: u64) -> bool {
+        n > 0 && divisor_sum(n) > 2 * u128::from(n)
+    }
+}
+
+/// Numbers whose proper divisors add up to less than the number.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deficient;
+
+impl NumberPredicate for Deficient {
+    fn contains(&self, n: u64) -> bool {
+        n > 0 && divisor_sum(n) < 2 * u128::from(n)
+    }
+}
+
+/// Numbers whose proper divisors add up to exactly the number.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Perfect;
+
+impl NumberPredicate for Perfect {
+    fn contains(&self, n: u64) -> bool {
+        n > 0 && divisor_sum(n) == 2 * u128::from(n)
+    }
+}
+
+/// Products of exactly two primes (counting repeats, so 4 and 6 are both semiprimes).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Semiprimes;
+
+impl NumberPredicate for Semiprimes {
+    fn contains(&self, n: u64) -> bool {
+        AlmostPrimes(2).contains(n)
+    }
+}
+
+/// Products of exactly k primes counting repeats, `AlmostPrimes(1)` is the primes.
+#[derive(Debug, Clone, Copy)]
+pub struct AlmostPrimes(pub u32);
+
+impl NumberPredicate for AlmostPrimes {
+    fn contains(&self, n: u64) -> bool {
+        n > 1 && prime_factor_count(n, self.0 + 1) == self.0
+    }
+}
+
+/// Numbers that read the same both ways when written in the given base.
+#[derive(Debug, Clone, Copy)]
+pub struct Palindromes(pub u64);
+
+impl NumberPredicate for Palindromes {
+    fn contains(&self, n: u64) -> bool {
+        let base = self.0.max(2);
+        let mut digits = Vec::new();
+        let mut rest = n;
+        loop {
+            digits.push(rest % base);
+            rest /= base;
+            if rest == 0 {
+                break;
+            }
+        }
+        digits.iter().eq(digits.iter().rev())
+    }
+}
+
+fn is_square(n: u128) -> bool {
+    let root = (n as f64).sqrt() as u128;
+    // the float root can be off by one for big numbers.
+    (root.saturating_sub(1)..=root + 1).any(|r| r * r == n)
+}
+
+// Sum of every divisor of n, including n itself.
+fn divisor_sum(mut n: u64) -> u128 {
+    let mut sum: u128 = 1;
+    let mut p = 2;
+    while p <= n / p {
+        if n.is_multiple_of(p) {
+            let mut power: u128 = 1;
+            let mut term: u128 = 1;
+            while n.is_multiple_of(p) {
+                n /= p;
+                power *= u128::from(p);
+                term += power;
+            }
+            sum *= term;
+        }
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        sum *= u128::from(n) + 1;
+    }
+    sum
+}
+
+// Number of divisors of n, including 1 and n.
+pub(crate) fn divisor_count(mut n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    let mut count = 1;
+    let mut p = 2;
+    while p <= n / p {
+        let mut power = 0;
+        while n.is_multiple_of(p) {
+            n /= p;
+            power += 1;
+        }
+        count *= power + 1;
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        count *= 2;
+    }
+    count
+}
+
+// Number of prime factors of n counting repeats, giving up once it reaches `stop`.
+fn prime_factor_count(mut n: u64, stop: u32) -> u32 {
+    let mut count = 0;
+    let mut p = 2;
+    while p <= n / p {
+        while n.is_multiple_of(p) {
+            n /= p;
+            count += 1;
+            if count >= stop {
+                return count;
+            }
+        }
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn members<P: NumberPredicate>(p: &P, below: u64) -> Vec<u64> {
+        (0..below).filter(|n| p.contains(*n)).collect()
+    }
+    #[test]
+    fn check_shapes() {
+        assert_eq!(members(&PerfectSquares, 30), vec![0, 1, 4, 9, 16, 25]);
+        assert_eq!(members(&Triangular, 30), vec![0, 1, 3, 6, 10, 15, 21, 28]);
+        assert_eq!(members(&Fibonacci, 30), vec![0, 1, 2, 3, 5, 8, 13, 21]);
+        assert!(PerfectSquares.contains(4_294_967_295 * 4_294_967_295));
+        assert!(Fibonacci.contains(12_200_160_415_121_876_738));
+    }
+    #[test]
+    fn check_divisors() {
+        assert_eq!(
+            members(&Abundant, 50),
+            vec![12, 18, 20, 24, 30, 36, 40, 42, 48]
+        );
+        assert_eq!(members(&Perfect, 500), vec![6, 28, 496]);
+        assert_eq!(members(&Deficient, 6), vec![1, 2, 3, 4, 5]);
+    }
+    #[test]
+    fn check_divisor_count() {
+        let counts: Vec<u64> = (0..13).map(divisor_count).collect();
+        assert_eq!(counts, vec![0, 1, 2, 2, 3, 2, 4, 2, 4, 3, 4, 2, 6]);
+    }
+    #[test]
+    fn check_almost_primes() {
+        assert_eq!(
+            members(&Semiprimes, 27),
+            vec![4, 6, 9, 10, 14, 15, 21, 22, 25, 26]
+        );
+        assert_eq!(
+            members(&AlmostPrimes(1), 20),
+            vec![2, 3, 5, 7, 11, 13, 17, 19]
+        );
+        assert_eq!(
+            members(&AlmostPrimes(3), 31),
+            vec![8, 12, 18, 20, 27, 28, 30]
+        );
+    }
+    #[test]
+    fn check_palindromes() {
+        assert_eq!(
+            members(&Palindromes(10), 30),
+            vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 11, 22]
+        );
+        // 0, 1, 11, 101, 111, 1001, 1111
+        assert_eq!(members(&Palindromes(2), 16), vec![0, 1, 3, 5, 7, 9, 15]);
+    }
+    #[cfg(feature = "prime")]
+    #[test]
+    fn check_primes() {
+        let small = Primes::up_to(100);
+        assert_eq!(members(&small, 20), members(&Primes::new(), 20));
+        assert!(small.contains(1_000_003));
+        assert!(!small.contains(1_000_001));
+        let listed: Vec<u64> = small.members(10, 30).collect();
+        assert_eq!(listed, vec![11, 13, 17, 19, 23, 29]);
+        assert_eq!(listed, members(&small, 31)[4..]);
+        assert_eq!(small.members(90, 110).count(), 5);
+
+        // the same from a lister, whether it sieves or looks up
+        for primes in [Primes::new(), Primes::up_to(5_000)] {
+            let mut lister = primes.range_lister(5_000);
+            for (start, end) in [(0, 30), (10, 9), (90, 110), (4_900, 5_000), (2, 2)] {
+                let mut listed = Vec::new();
+                lister(start, end, &mut |p| listed.push(p));
+                let expected: Vec<u64> = (start..=end).filter(|n| primal::is_prime(*n)).collect();
+                assert_eq!(listed, expected);
+            }
+        }
+    }
+    #[test]
+    fn check_closure() {
+        let odd = |n: u64| n % 2 == 1;
+        assert_eq!(members(&odd, 6), vec![1, 3, 5]);
+        let mut listed = Vec::new();
+        odd.range_lister(100)(10, 15, &mut |n| listed.push(n));
+        assert_eq!(listed, vec![11, 13, 15]);
+    }
+}
@@ -11,7 +11,7 @@ pub fn get_ulam_point(c: &Coord) -> UlamPoint {
     let x = value_of_coord(c);
 
     UlamPoint {
-        value: x,
+        value: x.into(),
         quad: q,
         is_prime: primal::is_prime(x.into()),
     }
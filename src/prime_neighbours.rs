@@ -0,0 +1,159 @@
+/// Counting how many of the 8 surrounding cells are marked (usually prime) for every cell.
+use crate::{mask::Mask, predicate::NumberPredicate, Coord};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeighbourCounts {
+    pub top_left: Coord,
+    pub width: u32,
+    pub height: u32,
+    /// Number of marked neighbours (0 to 8) per cell, row by row from the top.
+    pub counts: Vec<u8>,
+    /// Whether the cell itself is marked.
+    pub marked: Vec<bool>,
+}
+
+/// `marked[n]` is the number of marked cells with `n` marked neighbours,
+/// `unmarked[n]` the same for cells that are not marked themselves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NeighbourHistogram {
+    pub marked: [u64; 9],
+    pub unmarked: [u64; 9],
+}
+
+impl NeighbourCounts {
+    /// Count the neighbours of every cell of the mask except the outer border,
+    /// since the cells around the border are not known.
+    pub fn from_mask(mask: &Mask) -> NeighbourCounts {
+        let width = mask.width.saturating_sub(2);
+        let height = mask.height.saturating_sub(2);
+        let mut counts = Vec::with_capacity((width * height) as usize);
+        let mut marked = Vec::with_capacity((width * height) as usize);
+        for row in 1..=height {
+            for col in 1..=width {
+                let c = mask.coord_at(col, row);
+                let n = c.neighbours().iter().filter(|n| mask.contains(n)).count();
+                counts.push(n as u8);
+                marked.push(mask.get(col, row));
+            }
+        }
+        NeighbourCounts {
+            top_left: mask.coord_at(1, 1),
+            width,
+            height,
+            counts,
+            marked,
+        }
+    }
+
+    /// Count the prime neighbours of every cell between `min` and `max` (inclusive).
+    /// # Examples
+    /// ```
+    /// use ulam::{prime_neighbours::NeighbourCounts, Coord};
+    /// let counts = NeighbourCounts::primes(Coord::new(0, 0), Coord::new(0, 0));
+    /// // 2, 3, 5 and 7 surround the center
+    /// assert_eq!(counts.counts, vec![4]);
+    /// ```
+    #[cfg(feature = "prime")]
+    pub fn primes(min: Coord, max: Coord) -> NeighbourCounts {
+        let mask = Mask::primes(
+            Coord::new(min.x - 1, min.y - 1),
+            Coord::new(max.x + 1, max.y + 1),
+        );
+        NeighbourCounts::from_mask(&mask)
+    }
+
+    /// Count the neighbours in the predicate of every cell between `min` and `max` (inclusive).
+    pub fn from_predicate<P: NumberPredicate + ?Sized>(
+        min: Coord,
+        max: Coord,
+        predicate: &P,
+    ) -> NeighbourCounts {
+        let mask = Mask::from_predicate(
+            Coord::new(min.x - 1, min.y - 1),
+            Coord::new(max.x + 1, max.y + 1),
+            predicate,
+        );
+        NeighbourCounts::from_mask(&mask)
+    }
+
+    /// The number of marked neighbours of a coordinate, if it is in range.
+    pub fn get(&self, c: &Coord) -> Option<u8> {
+        let col = c.x - self.top_left.x;
+        let row = self.top_left.y - c.y;
+        if col < 0 || row < 0 || col >= self.width as i32 || row >= self.height as i32 {
+            None
+        } else {
+            Some(self.counts[(row as u32 * self.width + col as u32) as usize])
+        }
+    }
+
+    pub fn histogram(&self) -> NeighbourHistogram {
+        let mut hist = NeighbourHistogram::default();
+        for (count, marked) in self.counts.iter().zip(&self.marked) {
+            if *marked {
+                hist.marked[*count as usize] += 1;
+            } else {
+                hist.unmarked[*count as usize] += 1;
+            }
+        }
+        hist
+    }
+
+    /// Draw the counts as a heatmap, black for no marked neighbours up to white for 8.
+    #[cfg(feature = "img")]
+    pub fn render(&self) -> crate::ulamspiral_img::GreyImage {
+        image::ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let count = self.counts[(y * self.width + x) as usize];
+            image::Luma([(u32::from(count) * 255 / 8) as u8])
+        })
+    }
+}
+
+impl NeighbourHistogram {
+    /// Mean number of marked neighbours for marked and unmarked cells.
+    pub fn means(&self) -> (f64, f64) {
+        let mean = |h: &[u64; 9]| {
+            let total: u64 = h.iter().sum();
+            let weighted: u64 = h.iter().enumerate().map(|(n, c)| n as u64 * c).sum();
+            if total == 0 {
+                0.0
+            } else {
+                weighted as f64 / total as f64
+            }
+        };
+        (mean(&self.marked), mean(&self.unmarked))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_counts() {
+        let mask = Mask::from_fn(Coord::new(-2, -2), Coord::new(2, 2), |v| v % 2 == 1);
+        let counts = NeighbourCounts::from_mask(&mask);
+        assert_eq!(counts.width, 3);
+        assert_eq!(counts.top_left, Coord::new(-1, 1));
+        // 0 is surrounded by 1 3 5 7
+        assert_eq!(counts.get(&Coord::new(0, 0)), Some(4));
+        // 1 is surrounded by 11 9 and 3 7
+        assert_eq!(counts.get(&Coord::new(1, 0)), Some(4));
+        assert_eq!(counts.get(&Coord::new(2, 0)), None);
+    }
+    #[test]
+    fn check_histogram() {
+        let mask = Mask::from_fn(Coord::new(-2, -2), Coord::new(2, 2), |v| v % 2 == 1);
+        let hist = NeighbourCounts::from_mask(&mask).histogram();
+        assert_eq!(hist.marked.iter().sum::<u64>(), 4);
+        assert_eq!(hist.unmarked.iter().sum::<u64>(), 5);
+        assert_eq!(hist.means().0, 4.0);
+    }
+    #[cfg(feature = "img")]
+    #[test]
+    fn check_render() {
+        let counts = NeighbourCounts::primes(Coord::new(-1, -1), Coord::new(1, 1));
+        let img = counts.render();
+        assert_eq!(img.dimensions(), (3, 3));
+        assert_eq!(img.get_pixel(1, 1), &image::Luma([127]));
+    }
+}
@@ -0,0 +1,172 @@
+/// Where cells land in a rendered image and which cell is under a pixel, for interactive viewers.
+use std::ops::Range;
+
+use crate::{calc_coord::calc_coord_u64, value_of_coord_u64, viewport::Viewport, Coord};
+
+/// The mapping between pixels of a render of `viewport` and cells of the spiral,
+/// the same placement the renderers use. A downsampled pixel covers a whole block of cells.
+/// # Examples
+/// ```
+/// use ulam::{projection::Projection, viewport::Viewport, Coord};
+/// let projection = Projection::new(&Viewport::new(100, 100).with_scale(3));
+/// // 0 is in the middle, 3 pixels a cell
+/// assert_eq!(projection.value_at(148, 150), Some(0));
+/// assert_eq!(projection.pixels_of_value(1), Some((150..153, 150..153)));
+/// assert_eq!(projection.value_at(300, 0), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Projection {
+    pub viewport: Viewport,
+}
+
+impl Projection {
+    /// A `scale` or `downsample` of 0 is taken as 1.
+    pub fn new(viewport: &Viewport) -> Projection {
+        Projection {
+            viewport: Viewport {
+                scale: viewport.scale.max(1),
+                downsample: viewport.downsample.max(1),
+                ..*viewport
+            },
+        }
+    }
+
+    fn block(&self) -> u32 {
+        self.viewport.downsample
+    }
+
+    /// The (col, row) in the viewport of the cell under a pixel, the top left cell of its
+    /// block when downsampled. `None` off the image.
+    pub fn cell_at(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let (scale, block) = (self.viewport.scale, self.block());
+        let col = (x / scale).checked_mul(block)?;
+        let row = (y / scale).checked_mul(block)?;
+        (col < self.viewport.width && row < self.viewport.height).then_some((col, row))
+    }
+
+    /// The coordinate of the cell under a pixel.
+    pub fn coord_at(&self, x: u32, y: u32) -> Option<Coord> {
+        let (col, row) = self.cell_at(x, y)?;
+        Some(self.viewport.coord_at(col, row))
+    }
+
+    /// The value of the cell under a pixel.
+    pub fn value_at(&self, x: u32, y: u32) -> Option<u64> {
+        Some(value_of_coord_u64(&self.coord_at(x, y)?))
+    }
+
+    /// Every cell under a pixel, as ranges of viewport columns and rows.
+    /// One cell unless downsampled, blocks on the right and bottom edges can be cut short.
+    pub fn cells_at(&self, x: u32, y: u32) -> Option<(Range<u32>, Range<u32>)> {
+        let (col, row) = self.cell_at(x, y)?;
+        let block = self.block();
+        Some((
+            col..col.saturating_add(block).min(self.viewport.width),
+            row..row.saturating_add(block).min(self.viewport.height),
+        ))
+    }
+
+    /// The pixels a cell is drawn on, as ranges of x and y. `None` out of view.
+    pub fn pixels_of(&self, c: &Coord) -> Option<(Range<u32>, Range<u32>)> {
+        let (col, row) = self.viewport.cell_of(c)?;
+        let (scale, block) = (self.viewport.scale, self.block());
+        let (x, y) = (col / block * scale, row / block * scale);
+        Some((x..x + scale, y..y + scale))
+    }
+
+    pub fn pixels_of_value(&self, value: u64) -> Option<(Range<u32>, Range<u32>)> {
+        self.pixels_of(&calc_coord_u64(value))
+    }
+
+    /// A point in cell coordinates, where a cell's middle is at its `Coord`, as a pixel position.
+    /// It can be off the image, handy for drawing over a render.
+    pub fn to_pixel(&self, x: f64, y: f64) -> (f64, f64) {
+        let top_left = self.viewport.top_left();
+        let pixels_per_cell = f64::from(self.viewport.scale) / f64::from(self.block());
+        (
+            (x - f64::from(top_left.x) + 0.5) * pixels_per_cell,
+            (f64::from(top_left.y) - y + 0.5) * pixels_per_cell,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_cells_and_pixels() {
+        let projection = Projection::new(&Viewport::centered_on(Coord::new(10, -4), 7, 5));
+        assert_eq!(projection.coord_at(0, 0), Some(Coord::new(7, -2)));
+        assert_eq!(projection.coord_at(6, 4), Some(Coord::new(13, -6)));
+        assert_eq!(projection.coord_at(7, 0), None);
+        assert_eq!(
+            projection.pixels_of(&Coord::new(13, -6)),
+            Some((6..7, 4..5))
+        );
+        assert_eq!(projection.pixels_of(&Coord::new(0, 0)), None);
+        assert_eq!(projection.to_pixel(7.0, -2.0), (0.5, 0.5));
+
+        // 7 x 5 cells in blocks of 3, drawn 2 pixels a block
+        let projection = Projection::new(&Viewport::new(7, 5).with_downsample(3).with_scale(2));
+        assert_eq!(projection.cell_at(5, 3), Some((6, 3)));
+        assert_eq!(projection.cells_at(5, 3), Some((6..7, 3..5)));
+        assert_eq!(projection.cell_at(6, 0), None);
+        assert_eq!(projection.pixels_of(&Coord::new(3, -2)), Some((4..6, 2..4)));
+
+        // a scale of 0 is one pixel a cell both ways
+        let projection = Projection::new(&Viewport::new(7, 5).with_scale(0));
+        assert_eq!(projection.viewport.scale, 1);
+        let (xs, ys) = projection.pixels_of(&Coord::new(1, 1)).unwrap();
+        assert_eq!((xs.clone(), ys.clone()), (4..5, 1..2));
+        assert_eq!(
+            projection.coord_at(xs.start, ys.start),
+            Some(Coord::new(1, 1))
+        );
+    }
+    #[test]
+    #[cfg(feature = "img")]
+    fn check_against_render() {
+        use crate::{
+            predicate::{NumberPredicate, Primes},
+            ulamspiral_img::{render, render_density},
+        };
+        let primes = Primes::new();
+        for viewport in [
+            Viewport::new(31, 20).with_scale(3),
+            Viewport::centered_on(Coord::new(-200, 50), 17, 23).with_scale(2),
+        ] {
+            let img = render(&viewport, &primes).unwrap();
+            let projection = Projection::new(&viewport);
+            for (x, y, pixel) in img.enumerate_pixels() {
+                let value = projection.value_at(x, y).unwrap();
+                assert_eq!(pixel.0[0] == 255, primes.contains(value), "{x}, {y}");
+            }
+            // and back, one value at a time
+            let value = value_of_coord_u64(&viewport.coord_at(5, 7));
+            let one = render(&viewport, &|n: u64| n == value).unwrap();
+            let (xs, ys) = projection.pixels_of_value(value).unwrap();
+            for (x, y, pixel) in one.enumerate_pixels() {
+                assert_eq!(pixel.0[0] == 255, xs.contains(&x) && ys.contains(&y));
+            }
+        }
+        let viewport = Viewport::new(40, 30).with_downsample(4).with_scale(2);
+        let projection = Projection::new(&viewport);
+        let value = 321;
+        let one = render_density(
+            &viewport,
+            &|n: u64| n == value,
+            crate::density::ToneMap::Linear,
+        )
+        .unwrap();
+        let (xs, ys) = projection.pixels_of_value(value).unwrap();
+        for (x, y, pixel) in one.enumerate_pixels() {
+            let lit = xs.contains(&x) && ys.contains(&y);
+            assert_eq!(pixel.0[0] > 0, lit);
+            let (cols, rows) = projection.cells_at(x, y).unwrap();
+            let has = cols
+                .flat_map(|col| rows.clone().map(move |row| (col, row)))
+                .any(|(col, row)| value_of_coord_u64(&viewport.coord_at(col, row)) == value);
+            assert_eq!(has, lit);
+        }
+    }
+}
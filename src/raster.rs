@@ -0,0 +1,354 @@
+/// Drawing the spiral into plain byte buffers and netpbm files, for callers without the `image` crate.
+/// The pixels are the same as the `ulamspiral_img` renderers give.
+use std::{error::Error, io::Write};
+
+use crate::{
+    density::{DensityGrid, ToneMap},
+    mask::Mask,
+    palette::{over, Cell, ColourMapper},
+    predicate::NumberPredicate,
+    value_of_coord_u64,
+    viewport::Viewport,
+};
+
+/// Bytes per pixel of a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    Grey,
+    /// Red, green and blue, a grey render sets all three the same.
+    Rgb,
+}
+
+impl Channels {
+    pub fn bytes(&self) -> usize {
+        match self {
+            Channels::Grey => 1,
+            Channels::Rgb => 3,
+        }
+    }
+}
+
+/// Where to draw: `data` holds `viewport.pixel_height()` rows of pixels, each row starting
+/// `stride` bytes after the one before. Bytes past the end of a row are left alone.
+pub struct Buffer<'a> {
+    pub data: &'a mut [u8],
+    pub stride: usize,
+    pub channels: Channels,
+}
+
+impl Buffer<'_> {
+    // Check the buffer fits the viewport and give its size in pixels.
+    fn fit(&self, viewport: &Viewport) -> Result<(usize, usize), Box<dyn Error>> {
+        let width = viewport.pixel_width()? as usize;
+        let height = viewport.pixel_height()? as usize;
+        let row = width * self.channels.bytes();
+        if self.stride < row {
+            return Err(format!(
+                "stride {} is shorter than a row of {row} bytes",
+                self.stride
+            )
+            .into());
+        }
+        let needed = if height == 0 {
+            0
+        } else {
+            self.stride * (height - 1) + row
+        };
+        if self.data.len() < needed {
+            return Err(format!("buffer of {} bytes needs {needed}", self.data.len()).into());
+        }
+        Ok((width, height))
+    }
+
+    fn pixel(&mut self, x: usize, y: usize) -> &mut [u8] {
+        let bytes = self.channels.bytes();
+        &mut self.data[y * self.stride + x * bytes..][..bytes]
+    }
+
+    // Set every pixel from its (x, y).
+    fn paint<F: Fn(usize, usize) -> [u8; 3]>(&mut self, width: usize, height: usize, colour: F) {
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b] = colour(x, y);
+                match self.channels {
+                    Channels::Grey => self.pixel(x, y)[0] = r,
+                    Channels::Rgb => self.pixel(x, y).copy_from_slice(&[r, g, b]),
+                }
+            }
+        }
+    }
+}
+
+/// Same as `ulamspiral_img::render`: values in the predicate white on black, and downsampled
+/// viewports shaded by density.
+/// # Examples
+/// ```
+/// use ulam::{raster::{render_into, Buffer, Channels}, viewport::Viewport};
+/// let viewport = Viewport::new(64, 48);
+/// // rows padded to 128 bytes, as some framebuffers want
+/// let mut data = vec![0u8; 128 * 48];
+/// let mut buffer = Buffer { data: &mut data, stride: 128, channels: Channels::Grey };
+/// // any closure works, so this needs no features at all
+/// render_into(&mut buffer, &viewport, &|n: u64| n % 3 == 0).unwrap();
+/// ```
+pub fn render_into<P: NumberPredicate + ?Sized>(
+    buffer: &mut Buffer,
+    viewport: &Viewport,
+    predicate: &P,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = buffer.fit(viewport)?;
+    let scale = viewport.scale.max(1) as usize;
+    if viewport.downsample > 1 {
+        let grid = DensityGrid::new(viewport, predicate);
+        let levels = grid.tone(ToneMap::Linear);
+        buffer.paint(width, height, |x, y| {
+            let level = levels[(y / scale) * grid.width as usize + x / scale];
+            [level; 3]
+        });
+        return Ok(());
+    }
+    let mask = Mask::from_viewport(viewport, predicate);
+    buffer.paint(width, height, |x, y| {
+        if on_grid(viewport, x, y) {
+            [64; 3]
+        } else if mask.get((x / scale) as u32, (y / scale) as u32) {
+            [255; 3]
+        } else {
+            [0; 3]
+        }
+    });
+    Ok(())
+}
+
+/// Same as `ulamspiral_img::render_colour`, into an RGB buffer.
+pub fn render_colour_into<P, M>(
+    buffer: &mut Buffer,
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+) -> Result<(), Box<dyn Error>>
+where
+    P: NumberPredicate + ?Sized,
+    M: ColourMapper + ?Sized,
+{
+    if buffer.channels != Channels::Rgb {
+        return Err("colour renders need an RGB buffer".into());
+    }
+    if viewport.downsample > 1 {
+        return Err("colour renders are not downsampled, use render_into".into());
+    }
+    let (width, height) = buffer.fit(viewport)?;
+    let scale = viewport.scale.max(1) as usize;
+    let mask = Mask::from_viewport(viewport, predicate);
+    // one colour per cell, worked out once rather than for each of its pixels.
+    let colours: Vec<[u8; 3]> = (0..viewport.height)
+        .flat_map(|row| (0..viewport.width).map(move |col| (col, row)))
+        .map(|(col, row)| {
+            let coord = viewport.coord_at(col, row);
+            let cell = Cell::new(coord, value_of_coord_u64(&coord), mask.get(col, row));
+            over(mapper.colour(&cell), [0; 3])
+        })
+        .collect();
+    buffer.paint(width, height, |x, y| {
+        if on_grid(viewport, x, y) {
+            [64; 3]
+        } else {
+            colours[(y / scale) * viewport.width as usize + x / scale]
+        }
+    });
+    Ok(())
+}
+
+fn on_grid(viewport: &Viewport, x: usize, y: usize) -> bool {
+    viewport.on_grid_line(x as u32, y as u32)
+}
+
+/// A binary PGM (P5) of `render_into`.
+pub fn write_pgm<W, P>(
+    mut writer: W,
+    viewport: &Viewport,
+    predicate: &P,
+) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    P: NumberPredicate + ?Sized,
+{
+    let (width, height) = (viewport.pixel_width()?, viewport.pixel_height()?);
+    let mut data = vec![0; width as usize * height as usize];
+    render_into(
+        &mut buffer(&mut data, width, Channels::Grey),
+        viewport,
+        predicate,
+    )?;
+    write!(writer, "P5\n{width} {height}\n255\n")?;
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+/// A binary PBM (P4) of `render_into`, one bit a pixel. PBM draws set bits black,
+/// so the values in the predicate are the clear bits, and grid lines are black.
+/// # Examples
+/// ```
+/// use ulam::{raster::write_pbm, viewport::Viewport};
+/// let mut pbm = Vec::new();
+/// write_pbm(&mut pbm, &Viewport::new(10, 10), &|n: u64| n % 3 == 0).unwrap();
+/// assert!(pbm.starts_with(b"P4\n10 10\n"));
+/// // rows are padded to whole bytes
+/// assert_eq!(pbm.len(), 9 + 2 * 10);
+/// ```
+pub fn write_pbm<W, P>(
+    mut writer: W,
+    viewport: &Viewport,
+    predicate: &P,
+) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    P: NumberPredicate + ?Sized,
+{
+    let (width, height) = (viewport.pixel_width()?, viewport.pixel_height()?);
+    let mut data = vec![0; width as usize * height as usize];
+    render_into(
+        &mut buffer(&mut data, width, Channels::Grey),
+        viewport,
+        predicate,
+    )?;
+    write!(writer, "P4\n{width} {height}\n")?;
+    let mut bits = vec![0u8; (width as usize).div_ceil(8)];
+    for row in data.chunks(width.max(1) as usize) {
+        bits.fill(0);
+        for (x, level) in row.iter().enumerate() {
+            if *level < 128 {
+                bits[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        writer.write_all(&bits)?;
+    }
+    Ok(())
+}
+
+/// A binary PPM (P6) of `render_colour_into`.
+pub fn write_ppm<W, P, M>(
+    mut writer: W,
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    P: NumberPredicate + ?Sized,
+    M: ColourMapper + ?Sized,
+{
+    let (width, height) = (viewport.pixel_width()?, viewport.pixel_height()?);
+    let mut data = vec![0; width as usize * height as usize * 3];
+    render_colour_into(
+        &mut buffer(&mut data, width, Channels::Rgb),
+        viewport,
+        predicate,
+        mapper,
+    )?;
+    write!(writer, "P6\n{width} {height}\n255\n")?;
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+// A tightly packed buffer `width` pixels across.
+fn buffer(data: &mut [u8], width: u32, channels: Channels) -> Buffer<'_> {
+    Buffer {
+        data,
+        stride: width as usize * channels.bytes(),
+        channels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::ModSix;
+    fn primes() -> impl Fn(u64) -> bool {
+        |n: u64| {
+            n > 1
+                && (2..)
+                    .take_while(|d| d * d <= n)
+                    .all(|d| !n.is_multiple_of(d))
+        }
+    }
+    #[test]
+    fn check_stride_and_channels() {
+        let viewport = Viewport::new(5, 3);
+        // 2 bytes of padding a row, which stay as they were
+        let mut data = vec![7u8; 7 * 3];
+        let mut grey = Buffer {
+            data: &mut data,
+            stride: 7,
+            channels: Channels::Grey,
+        };
+        render_into(&mut grey, &viewport, &primes()).unwrap();
+        assert_eq!(
+            data,
+            [
+                255, 0, 255, 255, 255, 7, 7, //
+                0, 255, 0, 0, 0, 7, 7, //
+                255, 0, 255, 0, 0, 7, 7,
+            ]
+        );
+        let mut data = vec![0u8; 15 * 3];
+        let mut rgb = buffer(&mut data, 5, Channels::Rgb);
+        render_into(&mut rgb, &viewport, &primes()).unwrap();
+        assert_eq!(&data[9..12], [255, 255, 255]);
+
+        let mut short = vec![0u8; 14];
+        let mut too_small = buffer(&mut short, 5, Channels::Grey);
+        assert!(render_into(&mut too_small, &viewport, &primes()).is_err());
+        let mut data = vec![0u8; 100];
+        let narrow = &mut Buffer {
+            data: &mut data,
+            stride: 4,
+            channels: Channels::Grey,
+        };
+        assert!(render_into(narrow, &viewport, &primes()).is_err());
+        narrow.stride = 15;
+        assert!(render_colour_into(narrow, &viewport, &primes(), &ModSix).is_err());
+    }
+    #[test]
+    fn check_netpbm() {
+        let viewport = Viewport::new(5, 3);
+        let mut pgm = Vec::new();
+        write_pgm(&mut pgm, &viewport, &primes()).unwrap();
+        assert!(pgm.starts_with(b"P5\n5 3\n255\n"));
+        assert_eq!(pgm.len(), 11 + 15);
+        let mut pbm = Vec::new();
+        write_pbm(&mut pbm, &viewport, &primes()).unwrap();
+        // 17, 4, 3, 2 and 11 along the top, the set bits are the composites
+        assert_eq!(pbm, b"P4\n5 3\n\x40\xb8\x58");
+        let mut ppm = Vec::new();
+        write_ppm(&mut ppm, &viewport, &primes(), &ModSix).unwrap();
+        assert!(ppm.starts_with(b"P6\n5 3\n255\n"));
+        assert_eq!(ppm.len(), 11 + 45);
+        // too many pixels across for a u32
+        let wide = Viewport::new(70_000, 1).with_scale(70_000);
+        assert!(write_pgm(Vec::new(), &wide, &primes()).is_err());
+    }
+    #[test]
+    #[cfg(feature = "img")]
+    fn check_same_as_image() {
+        use crate::{predicate::Primes, ulamspiral_img, Coord};
+        let primes = Primes::new();
+        for viewport in [
+            Viewport::new(40, 30).with_scale(3).with_grid_lines(true),
+            Viewport::centered_on(Coord::new(300, -20), 33, 21),
+            Viewport::new(100, 90).with_downsample(7).with_scale(2),
+        ] {
+            let mut pgm = Vec::new();
+            write_pgm(&mut pgm, &viewport, &primes).unwrap();
+            let img = ulamspiral_img::render(&viewport, &primes).unwrap();
+            assert_eq!(image::load_from_memory(&pgm).unwrap().into_luma8(), img);
+        }
+        let viewport = Viewport::new(30, 20).with_scale(2).with_grid_lines(true);
+        let mut ppm = Vec::new();
+        write_ppm(&mut ppm, &viewport, &primes, &ModSix).unwrap();
+        assert_eq!(
+            image::load_from_memory(&ppm).unwrap().into_rgb8(),
+            ulamspiral_img::render_colour(&viewport, &primes, &ModSix).unwrap()
+        );
+    }
+}
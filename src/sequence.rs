@@ -0,0 +1,148 @@
+/// Loading integer sequences (OEIS b-files or plain lists) to highlight on the spiral.
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+    num::IntErrorKind,
+    path::Path,
+};
+
+use crate::predicate::NumberPredicate;
+
+/// A set of values to highlight, usable anywhere a `NumberPredicate` is.
+/// Negative terms and terms too big for a `u64` are dropped since they never show up on the spiral.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegerSet {
+    pub values: HashSet<u64>,
+}
+
+impl IntegerSet {
+    /// Read the OEIS b-file format, one `n a(n)` pair per line.
+    /// Blank lines and lines starting with `#` are skipped.
+    /// # Examples
+    /// ```
+    /// use ulam::{predicate::NumberPredicate, sequence::IntegerSet};
+    /// let bfile = "# A000045\n0 0\n1 1\n2 1\n3 2\n4 3\n5 5\n";
+    /// let fib = IntegerSet::from_bfile(bfile.as_bytes()).unwrap();
+    /// assert!(fib.contains(5));
+    /// assert!(!fib.contains(4));
+    /// ```
+    pub fn from_bfile<R: BufRead>(reader: R) -> Result<IntegerSet, Box<dyn Error>> {
+        let mut set = IntegerSet::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (Some(_), Some(term), None) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(format!("line {}: expected `n a(n)`, got {line:?}", i + 1).into());
+            };
+            set.insert_term(term)?;
+        }
+        Ok(set)
+    }
+
+    /// Read a plain list of integers separated by commas, spaces or new lines.
+    /// # Examples
+    /// ```
+    /// use ulam::{predicate::NumberPredicate, sequence::IntegerSet};
+    /// let set = IntegerSet::from_list("1, 4, 9,\n16 25".as_bytes()).unwrap();
+    /// assert_eq!(set.values.len(), 5);
+    /// assert!(set.contains(16));
+    /// ```
+    pub fn from_list<R: BufRead>(reader: R) -> Result<IntegerSet, Box<dyn Error>> {
+        let mut set = IntegerSet::default();
+        for line in reader.lines() {
+            for term in line?.split(|c: char| c == ',' || c.is_whitespace()) {
+                if !term.is_empty() {
+                    set.insert_term(term)?;
+                }
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn open_bfile<P: AsRef<Path>>(path: P) -> Result<IntegerSet, Box<dyn Error>> {
+        IntegerSet::from_bfile(BufReader::new(File::open(path)?))
+    }
+
+    pub fn open_list<P: AsRef<Path>>(path: P) -> Result<IntegerSet, Box<dyn Error>> {
+        IntegerSet::from_list(BufReader::new(File::open(path)?))
+    }
+
+    fn insert_term(&mut self, term: &str) -> Result<(), Box<dyn Error>> {
+        // a term that gets dropped still has to be a number.
+        let magnitude = term.strip_prefix('-');
+        let digits = magnitude.unwrap_or(term);
+        let all_digits = || digits.bytes().all(|b| b.is_ascii_digit());
+        match digits.parse::<u64>() {
+            Ok(value) if magnitude.is_none() => {
+                self.values.insert(value);
+            }
+            Ok(_) => {}
+            // too big, as long as it is all digits.
+            Err(e) if *e.kind() == IntErrorKind::PosOverflow && all_digits() => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+}
+
+impl FromIterator<u64> for IntegerSet {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> IntegerSet {
+        IntegerSet {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl NumberPredicate for IntegerSet {
+    fn contains(&self, n: u64) -> bool {
+        self.values.contains(&n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_bfile() {
+        let bfile = "# comment\n\n1 2\n2 -3\n3 10\n";
+        let set = IntegerSet::from_bfile(bfile.as_bytes()).unwrap();
+        assert_eq!(set, IntegerSet::from_iter([2, 10]));
+
+        // Fibonacci outgrows u64 at a(94), the rest of the file still loads
+        let bfile = "93 12200160415121876738\n94 19740274219868223167\n95 31940434634990099905\n";
+        let set = IntegerSet::from_bfile(bfile.as_bytes()).unwrap();
+        assert_eq!(set, IntegerSet::from_iter([12_200_160_415_121_876_738]));
+        let set = IntegerSet::from_list("-99999999999999999999999999999999999999999, 4".as_bytes());
+        assert_eq!(set.unwrap(), IntegerSet::from_iter([4]));
+    }
+    #[test]
+    fn check_bad_bfile() {
+        assert!(IntegerSet::from_bfile("1 2 3\n".as_bytes()).is_err());
+        assert!(IntegerSet::from_bfile("1\n".as_bytes()).is_err());
+        assert!(IntegerSet::from_bfile("1 x\n".as_bytes()).is_err());
+        assert!(IntegerSet::from_bfile("1 99999999999999999999x\n".as_bytes()).is_err());
+        assert!(IntegerSet::from_bfile("1 --2\n".as_bytes()).is_err());
+    }
+    #[test]
+    fn check_list() {
+        let set = IntegerSet::from_list("3,5,,7\r\n11 13\n".as_bytes()).unwrap();
+        assert_eq!(set, IntegerSet::from_iter([3, 5, 7, 11, 13]));
+        assert!(IntegerSet::from_list("3;5".as_bytes()).is_err());
+    }
+    #[test]
+    fn check_mask() {
+        let set = IntegerSet::from_list("1 2 3".as_bytes()).unwrap();
+        let mask = crate::mask::Mask::from_predicate(
+            crate::Coord::new(-1, -1),
+            crate::Coord::new(1, 1),
+            &set,
+        );
+        assert_eq!(mask.count(), 3);
+    }
+}
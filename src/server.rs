@@ -0,0 +1,348 @@
+/// A small HTTP server for tiles and spiral lookups, run by the `ulam-server` binary.
+///
+/// - `/tiles/{z}/{x}/{y}.png` a tile from `tiles::Tiler`, rendered on first request and cached on disk
+/// - `/coord?value=n` the `Coord` of a value
+/// - `/value?x=&y=` the value at a coordinate
+/// - `/point?x=&y=` the `UlamPoint` at a coordinate
+/// - `/region?x0=&y0=&x1=&y1=` every value between two corners, row by row from the top
+use std::{collections::HashMap, error::Error, fs, io::Cursor, path::PathBuf, str::FromStr};
+
+use serde::Serialize;
+use tiny_http::{Header, Response};
+
+use crate::{
+    calc_coord::calc_coord_u64,
+    predicate::{NumberPredicate, Primes},
+    quad_of_coord,
+    tiles::{tile_path, Tiler},
+    value_of_coord_u64,
+    viewport::Viewport,
+    Coord, UlamPoint,
+};
+
+/// Regions bigger than this many cells are refused.
+pub const MAX_REGION: u64 = 1 << 20;
+
+/// Coordinates further out than this are refused, past it values no longer fit the arithmetic.
+pub const MAX_COORD: u32 = 1 << 30;
+
+/// The last value of the ring `MAX_COORD` out, larger values are refused.
+pub const MAX_VALUE: u64 = (2 * MAX_COORD as u64 + 1).pow(2) - 1;
+
+/// The answer to one request, kept apart from `tiny_http` so routes can be tested on their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    fn json<T: Serialize>(value: &T) -> Reply {
+        match serde_json::to_vec(value) {
+            Ok(body) => Reply {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Reply::error(500, &e.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Reply {
+        Reply {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message })
+                .to_string()
+                .into_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Region {
+    pub min: Coord,
+    pub max: Coord,
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<u64>,
+    pub primes: Vec<u64>,
+}
+
+pub struct TileServer {
+    pub tiler: Tiler,
+    pub cache_dir: PathBuf,
+    primes: Primes,
+}
+
+impl TileServer {
+    pub fn new(tiler: Tiler, cache_dir: PathBuf) -> TileServer {
+        TileServer {
+            tiler,
+            cache_dir,
+            primes: Primes::new(),
+        }
+    }
+
+    /// Answer requests on `server` until it shuts down.
+    /// # Examples
+    /// ```no_run
+    /// use ulam::{server::TileServer, tiles::Tiler};
+    /// let server = tiny_http::Server::http("127.0.0.1:8080").unwrap();
+    /// TileServer::new(Tiler::new(10_000).unwrap(), "tile-cache".into()).serve(&server).unwrap();
+    /// ```
+    pub fn serve(&self, server: &tiny_http::Server) -> Result<(), Box<dyn Error>> {
+        for request in server.incoming_requests() {
+            let reply = self.route(request.url());
+            let header = Header::from_bytes("Content-Type", reply.content_type)
+                .map_err(|_| "bad content type")?;
+            let length = reply.body.len();
+            let response = Response::new(
+                reply.status.into(),
+                vec![header],
+                Cursor::new(reply.body),
+                Some(length),
+                None,
+            );
+            // a client hanging up early is not the server's problem.
+            let _ = request.respond(response);
+        }
+        Ok(())
+    }
+
+    /// Answer one request url, path and query.
+    pub fn route(&self, url: &str) -> Reply {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let query: HashMap<&str, &str> = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .collect();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        let result = match segments.as_slice() {
+            ["tiles", z, x, y] => return self.tile(z, x, y),
+            ["coord"] => value_param(&query, "value").map(|v| Reply::json(&calc_coord_u64(v))),
+            ["value"] => coord_param(&query, "x", "y")
+                .map(|c| Reply::json(&serde_json::json!({ "value": value_of_coord_u64(&c) }))),
+            ["point"] => coord_param(&query, "x", "y").map(|c| {
+                let value = value_of_coord_u64(&c);
+                Reply::json(&UlamPoint {
+                    value,
+                    quad: quad_of_coord(&c),
+                    is_prime: self.primes.contains(value),
+                })
+            }),
+            ["region"] => self.region(&query).map(|r| Reply::json(&r)),
+            _ => return Reply::error(404, "not found"),
+        };
+        result.unwrap_or_else(|e| Reply::error(400, &e.to_string()))
+    }
+
+    fn tile(&self, z: &str, x: &str, y: &str) -> Reply {
+        let (Ok(z), Ok(x), Some(Ok(y))) = (
+            z.parse::<u32>(),
+            x.parse::<u32>(),
+            y.strip_suffix(".png").map(str::parse::<u32>),
+        ) else {
+            return Reply::error(404, "not found");
+        };
+        if z > self.tiler.max_zoom || !self.tiler.in_spiral(z, x, y) {
+            return Reply::error(404, "no such tile");
+        }
+        let path = tile_path(&self.cache_dir, z, x, y);
+        let cached = if path.exists() {
+            Ok(path)
+        } else {
+            self.tiler
+                .write_tile(&self.cache_dir, z, x, y, &self.primes)
+        };
+        match cached.and_then(|path| Ok(fs::read(path)?)) {
+            Ok(body) => Reply {
+                status: 200,
+                content_type: "image/png",
+                body,
+            },
+            Err(e) => Reply::error(500, &e.to_string()),
+        }
+    }
+
+    fn region(&self, query: &HashMap<&str, &str>) -> Result<Region, Box<dyn Error>> {
+        let a = coord_param(query, "x0", "y0")?;
+        let b = coord_param(query, "x1", "y1")?;
+        let min = Coord::new(a.x.min(b.x), a.y.min(b.y));
+        let max = Coord::new(a.x.max(b.x), a.y.max(b.y));
+        let width = u64::from(max.x.abs_diff(min.x)) + 1;
+        let height = u64::from(max.y.abs_diff(min.y)) + 1;
+        if width * height > MAX_REGION {
+            return Err(format!("region is over {MAX_REGION} cells").into());
+        }
+        let viewport =
+            Viewport::from_top_left(Coord::new(min.x, max.y), width as u32, height as u32);
+        let values: Vec<u64> = (0..viewport.height)
+            .flat_map(|row| (0..viewport.width).map(move |col| (col, row)))
+            .map(|(col, row)| value_of_coord_u64(&viewport.coord_at(col, row)))
+            .collect();
+        let primes = values
+            .iter()
+            .copied()
+            .filter(|v| self.primes.contains(*v))
+            .collect();
+        Ok(Region {
+            min,
+            max,
+            width: viewport.width,
+            height: viewport.height,
+            values,
+            primes,
+        })
+    }
+}
+
+fn param<T: FromStr>(query: &HashMap<&str, &str>, name: &str) -> Result<T, Box<dyn Error>>
+where
+    T::Err: Error + 'static,
+{
+    let value = query.get(name).ok_or(format!("missing `{name}`"))?;
+    Ok(value.parse()?)
+}
+
+fn value_param(query: &HashMap<&str, &str>, name: &str) -> Result<u64, Box<dyn Error>> {
+    let value = param(query, name)?;
+    if value > MAX_VALUE {
+        return Err(format!("values are limited to {MAX_VALUE}").into());
+    }
+    Ok(value)
+}
+
+fn coord_param(query: &HashMap<&str, &str>, x: &str, y: &str) -> Result<Coord, Box<dyn Error>> {
+    let c = Coord::new(param(query, x)?, param(query, y)?);
+    if c.x.unsigned_abs().max(c.y.unsigned_abs()) > MAX_COORD {
+        return Err(format!("coordinates are limited to {MAX_COORD} cells out").into());
+    }
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    fn server(name: &str) -> TileServer {
+        let dir = std::env::temp_dir().join(format!("ulam-server-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        TileServer::new(Tiler::new(100).unwrap(), dir)
+    }
+    fn json(reply: &Reply) -> serde_json::Value {
+        assert_eq!(
+            reply.status,
+            200,
+            "{}",
+            String::from_utf8_lossy(&reply.body)
+        );
+        serde_json::from_slice(&reply.body).unwrap()
+    }
+    #[test]
+    fn check_lookups() {
+        let server = server("lookups");
+        let coord = json(&server.route("/coord?value=10"));
+        assert_eq!(coord, serde_json::json!({ "x": 2, "y": 0 }));
+        let value = json(&server.route("/value?x=-100000&y=100000"));
+        assert_eq!(value["value"], 40_000_000_000u64);
+        let point = json(&server.route("/point?x=1&y=1"));
+        assert_eq!(point["value"], 2);
+        assert_eq!(point["is_prime"], true);
+        assert_eq!(point["quad"], "NorthEast");
+        // past where the u32 values of `get_ulam_point` overflow
+        let far = json(&server.route("/point?x=30000&y=0"));
+        assert_eq!(far["value"], 3_599_910_000u64);
+        assert_eq!(far["quad"], "East");
+        assert_eq!(far["is_prime"], false);
+        let far: UlamPoint =
+            serde_json::from_value(json(&server.route("/point?x=-100000&y=100000"))).unwrap();
+        assert_eq!(far.value, 40_000_000_000);
+        assert_eq!(far.quad, crate::Quad::NorthWest);
+        assert_eq!(server.route("/point?x=-2147483648&y=0").status, 400);
+        // the last value the server answers is on the far corner of the last ring
+        let last = json(&server.route(&format!("/coord?value={MAX_VALUE}")));
+        assert_eq!(
+            last,
+            serde_json::json!({ "x": MAX_COORD, "y": -(MAX_COORD as i64) })
+        );
+        let past = server.route(&format!("/coord?value={}", MAX_VALUE + 1));
+        assert_eq!(past.status, 400);
+        assert_eq!(
+            server.route("/coord?value=18446744073709551615").status,
+            400
+        );
+        assert_eq!(server.route("/value?x=0&y=2000000000").status, 400);
+        assert_eq!(server.route("/value?x=1").status, 400);
+        assert_eq!(server.route("/value?x=1&y=z").status, 400);
+        assert_eq!(server.route("/nothing").status, 404);
+    }
+    #[test]
+    fn check_region() {
+        let server = server("region");
+        let region = json(&server.route("/region?x0=1&y0=-1&x1=-1&y1=1"));
+        assert_eq!(region["width"], 3);
+        assert_eq!(
+            region["values"],
+            serde_json::json!([4, 3, 2, 5, 0, 1, 6, 7, 8])
+        );
+        assert_eq!(region["primes"], serde_json::json!([3, 2, 5, 7]));
+        assert_eq!(
+            server.route("/region?x0=0&y0=0&x1=5000&y1=5000").status,
+            400
+        );
+    }
+    #[test]
+    fn check_tiles() {
+        let server = server("tiles");
+        let reply = server.route("/tiles/1/0/1.png");
+        assert_eq!((reply.status, reply.content_type), (200, "image/png"));
+        let cached = tile_path(&server.cache_dir, 1, 0, 1);
+        assert_eq!(fs::read(&cached).unwrap(), reply.body);
+        assert_eq!(server.route("/tiles/1/0/1.png"), reply);
+        assert_eq!(server.route("/tiles/1/2/0.png").status, 404);
+        assert_eq!(server.route("/tiles/9/0/0.png").status, 404);
+        assert_eq!(server.route("/tiles/1/0/x.png").status, 404);
+        fs::remove_dir_all(&server.cache_dir).unwrap();
+    }
+    #[test]
+    fn check_localhost() {
+        let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = http.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || {
+            let _ = server("localhost").serve(&http);
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET /coord?value=3 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("Content-Type: application/json"));
+        assert!(response.ends_with(r#"{"x":0,"y":1}"#));
+
+        // still answering after points far out
+        for (url, status) in [
+            ("/point?x=30000&y=0", "200"),
+            ("/point?x=-2147483648&y=0", "400"),
+            ("/coord?value=18446744073709551615", "400"),
+            ("/coord?value=3", "200"),
+        ] {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "GET {url} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with(&format!("HTTP/1.1 {status}")), "{url}");
+        }
+    }
+}
@@ -0,0 +1,216 @@
+/// A segmented sieve for listing the primes in a range of values without sieving
+/// everything below it, so memory stays small even for values far past `u32`.
+use crate::calc_coord::isqrt;
+
+/// Values sieved per segment.
+const SEGMENT: u64 = 1 << 18;
+
+/// The primes from `start` up to and including `end`, in increasing order.
+/// # Examples
+/// ```
+/// use ulam::sieve::SegmentedPrimes;
+/// let primes: Vec<u64> = SegmentedPrimes::new(10_000_000_000, 10_000_000_100).collect();
+/// assert_eq!(primes[0], 10_000_000_019);
+/// assert_eq!(primes.len(), 5);
+/// ```
+pub struct SegmentedPrimes {
+    base: BasePrimes,
+    end: u64,
+    two: bool,
+    next_segment: Option<u64>,
+    // odd values only, `segment[i]` is `segment_start + 2 * i`.
+    segment: Vec<bool>,
+    segment_start: u64,
+    pos: usize,
+}
+
+impl SegmentedPrimes {
+    pub fn new(start: u64, end: u64) -> SegmentedPrimes {
+        SegmentedPrimes {
+            base: BasePrimes::new(end),
+            end,
+            two: start <= 2 && 2 <= end,
+            next_segment: Some(start),
+            segment: Vec::new(),
+            segment_start: start,
+            pos: 0,
+        }
+    }
+
+    // Sieve the next segment, returns false once past the end.
+    fn fill(&mut self) -> bool {
+        let Some(start) = self.next_segment.filter(|s| *s <= self.end) else {
+            return false;
+        };
+        let stop = self.end.min(start.saturating_add(SEGMENT - 1));
+        let first = start | 1;
+        self.base.cross_off(first, stop, &mut self.segment);
+        self.segment_start = first;
+        self.next_segment = stop.checked_add(1);
+        self.pos = 0;
+        true
+    }
+}
+
+impl Iterator for SegmentedPrimes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.two {
+            self.two = false;
+            return Some(2);
+        }
+        loop {
+            if let Some(i) = self.segment[self.pos..].iter().position(|prime| *prime) {
+                let i = self.pos + i;
+                self.pos = i + 1;
+                return Some(self.segment_start + 2 * i as u64);
+            }
+            self.pos = self.segment.len();
+            if !self.fill() {
+                return None;
+            }
+        }
+    }
+}
+
+/// The odd primes up to the square root of the largest value of interest, enough to sieve
+/// any range below it. Made once and shared, it spares sieving them again for every range.
+/// # Examples
+/// ```
+/// use ulam::sieve::BasePrimes;
+/// let base = BasePrimes::new(20_000_000_000);
+/// let mut scratch = Vec::new();
+/// let mut primes = Vec::new();
+/// for start in [10_000_000_000, 15_000_000_000] {
+///     base.primes_in(start, start + 100, &mut scratch, &mut |p| primes.push(p));
+/// }
+/// assert_eq!(primes[0], 10_000_000_019);
+/// assert_eq!(primes.len(), 10);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasePrimes {
+    max: u64,
+    primes: Vec<u64>,
+}
+
+impl BasePrimes {
+    pub fn new(max: u64) -> BasePrimes {
+        let root = isqrt(max);
+        let primes = primal::Sieve::new(root as usize)
+            .primes_from(0)
+            .skip(1)
+            .map(|p| p as u64)
+            .take_while(|p| *p <= root)
+            .collect();
+        BasePrimes { max, primes }
+    }
+
+    /// The largest value these can sieve up to.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Pass every prime from `start` up to and including `end` to `found`, in increasing order.
+    /// `scratch` is reused between calls to save allocating. `end` must be at most `max`.
+    pub fn primes_in(
+        &self,
+        start: u64,
+        end: u64,
+        scratch: &mut Vec<bool>,
+        found: &mut dyn FnMut(u64),
+    ) {
+        assert!(end <= self.max, "{end} is past the {} sieved for", self.max);
+        if start > end {
+            return;
+        }
+        if start <= 2 && 2 <= end {
+            found(2);
+        }
+        let first = start | 1;
+        self.cross_off(first, end, scratch);
+        for (i, _) in scratch.iter().enumerate().filter(|(_, prime)| **prime) {
+            found(first + 2 * i as u64);
+        }
+    }
+
+    // Fill `segment` with the odd values from `first` (odd) to `stop`, false where composite.
+    fn cross_off(&self, first: u64, stop: u64, segment: &mut Vec<bool>) {
+        segment.clear();
+        if first <= stop {
+            segment.resize(((stop - first) / 2 + 1) as usize, true);
+        }
+        if first == 1 && !segment.is_empty() {
+            segment[0] = false;
+        }
+        let len = segment.len() as u64;
+        for &p in &self.primes {
+            let square = p * p;
+            if square > stop {
+                break;
+            }
+            // index of the first odd multiple of p, first + 2j, that is not p itself.
+            let t = match first % p {
+                0 => 0,
+                r => p - r,
+            };
+            let mut j = if t % 2 == 0 { t / 2 } else { (t + p) / 2 };
+            if first + 2 * j < square {
+                j = (square - first) / 2;
+            }
+            while j < len {
+                segment[j as usize] = false;
+                j += p;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_small() {
+        let primes: Vec<u64> = SegmentedPrimes::new(0, 30).collect();
+        assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert_eq!(SegmentedPrimes::new(24, 28).count(), 0);
+        assert_eq!(SegmentedPrimes::new(5, 4).count(), 0);
+        assert_eq!(SegmentedPrimes::new(2, 2).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(SegmentedPrimes::new(3, 3).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(SegmentedPrimes::new(1, 1).count(), 0);
+        assert_eq!(SegmentedPrimes::new(8, 9).count(), 0);
+        for end in 0..100 {
+            let expected = (0..=end).filter(|n| primal::is_prime(*n)).count();
+            assert_eq!(SegmentedPrimes::new(0, end).count(), expected);
+        }
+    }
+    #[test]
+    fn check_segments() {
+        // crosses several segments
+        let count = SegmentedPrimes::new(0, 3 * SEGMENT + 17).count();
+        let sieve = primal::Sieve::new(3 * SEGMENT as usize + 17);
+        assert_eq!(count, sieve.prime_pi(3 * SEGMENT as usize + 17));
+        let from_middle: Vec<u64> = SegmentedPrimes::new(SEGMENT - 50, SEGMENT + 50).collect();
+        let expected: Vec<u64> = (SEGMENT - 50..=SEGMENT + 50)
+            .filter(|n| primal::is_prime(*n))
+            .collect();
+        assert_eq!(from_middle, expected);
+    }
+    #[test]
+    fn check_base() {
+        let base = BasePrimes::new(1_000_000);
+        let mut scratch = Vec::new();
+        for (start, end) in [
+            (0, 1_000),
+            (1, 2),
+            (999_000, 1_000_000),
+            (500_001, 500_001),
+            (7, 3),
+        ] {
+            let mut primes = Vec::new();
+            base.primes_in(start, end, &mut scratch, &mut |p| primes.push(p));
+            let expected: Vec<u64> = (start..=end).filter(|n| primal::is_prime(*n)).collect();
+            assert_eq!(primes, expected);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+/// The 2D Fourier magnitude spectrum of a mask, where periodic structure in the spiral,
+/// like primes avoiding residues mod small primes, shows up as peaks.
+use std::{
+    f64::consts::PI,
+    ops::{Add, Mul, Sub},
+};
+
+use crate::mask::Mask;
+#[cfg(feature = "img")]
+use crate::ulamspiral_img::GreyImage;
+
+/// Magnitudes of the discrete Fourier transform, row by row from the top with the zero
+/// frequency moved to the middle, at (`width / 2`, `height / 2`).
+/// The mean is taken off first so the zero frequency does not drown the rest out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    pub width: u32,
+    pub height: u32,
+    pub magnitudes: Vec<f64>,
+}
+
+impl Spectrum {
+    /// # Examples
+    /// ```
+    /// use ulam::{mask::Mask, spectrum::Spectrum, Coord};
+    /// // every fourth column, a period of 4 cells across 16
+    /// let mask = Mask::from_fn(Coord::new(0, -15), Coord::new(15, 0), |v| {
+    ///     ulam::calc_coord::calc_coord_u64(v).x % 4 == 0
+    /// });
+    /// let spectrum = Spectrum::from_mask(&mask);
+    /// assert!(spectrum.get(8 + 4, 8) > 50.0);
+    /// assert!(spectrum.get(8 + 3, 8) < 1e-9);
+    /// ```
+    pub fn from_mask(mask: &Mask) -> Spectrum {
+        let levels = (0..mask.height)
+            .flat_map(|row| (0..mask.width).map(move |col| mask.get(col, row)))
+            .map(|on| if on { 1.0 } else { 0.0 })
+            .collect();
+        Spectrum::from_levels(mask.width, mask.height, levels)
+    }
+
+    /// The spectrum of an image such as `ulamspiral_img::generate` makes, white as 1.
+    #[cfg(feature = "img")]
+    pub fn from_image(img: &GreyImage) -> Spectrum {
+        let levels = img.pixels().map(|p| f64::from(p.0[0]) / 255.0).collect();
+        Spectrum::from_levels(img.width(), img.height(), levels)
+    }
+
+    /// The spectrum of `width` x `height` levels, row by row from the top.
+    pub fn from_levels(width: u32, height: u32, levels: Vec<f64>) -> Spectrum {
+        let (w, h) = (width as usize, height as usize);
+        assert_eq!(levels.len(), w * h, "levels must fill the width and height");
+        let mean = levels.iter().sum::<f64>() / levels.len().max(1) as f64;
+        let mut data: Vec<Complex> = levels
+            .into_iter()
+            .map(|l| Complex::new(l - mean, 0.0))
+            .collect();
+        for row in data.chunks_mut(w.max(1)) {
+            dft(row);
+        }
+        let mut column = vec![Complex::default(); h];
+        for x in 0..w {
+            for y in 0..h {
+                column[y] = data[y * w + x];
+            }
+            dft(&mut column);
+            for y in 0..h {
+                data[y * w + x] = column[y];
+            }
+        }
+        let mut magnitudes = vec![0.0; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                magnitudes[((y + h / 2) % h) * w + (x + w / 2) % w] = data[y * w + x].norm();
+            }
+        }
+        Spectrum {
+            width,
+            height,
+            magnitudes,
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> f64 {
+        self.magnitudes[(y * self.width + x) as usize]
+    }
+
+    /// The frequency at a position, in cycles across the whole width and height.
+    /// Negative to the left of and above the middle.
+    pub fn frequency_of(&self, x: u32, y: u32) -> (i32, i32) {
+        (
+            x as i32 - (self.width / 2) as i32,
+            y as i32 - (self.height / 2) as i32,
+        )
+    }
+
+    /// The `count` strongest positions with their magnitudes, strongest first.
+    /// Each peak comes with its mirror image, the input being real.
+    pub fn peaks(&self, count: usize) -> Vec<(u32, u32, f64)> {
+        let mut all: Vec<(u32, u32, f64)> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y, self.get(x, y))))
+            .collect();
+        all.sort_by(|a, b| b.2.total_cmp(&a.2).then((a.1, a.0).cmp(&(b.1, b.0))));
+        all.truncate(count);
+        all
+    }
+
+    /// `ln(1 + magnitude)` scaled so the strongest is white.
+    #[cfg(feature = "img")]
+    pub fn render(&self) -> GreyImage {
+        let top = self.magnitudes.iter().copied().fold(0.0, f64::max).ln_1p();
+        GreyImage::from_fn(self.width, self.height, |x, y| {
+            let level = if top > 0.0 {
+                self.get(x, y).ln_1p() / top
+            } else {
+                0.0
+            };
+            image::Luma([(level * 255.0).round() as u8])
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    fn cis(angle: f64) -> Complex {
+        let (sin, cos) = angle.sin_cos();
+        Complex::new(cos, sin)
+    }
+
+    fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+// In place, any length. Powers of two go straight to the radix 2 FFT, the rest through
+// Bluestein's chirp, a convolution that is done with a longer power of two.
+fn dft(data: &mut [Complex]) {
+    let n = data.len();
+    if n <= 1 {
+        return;
+    }
+    if n.is_power_of_two() {
+        fft(data, false);
+        return;
+    }
+    let m = (2 * n - 1).next_power_of_two();
+    // k^2 taken mod 2n keeps the angle small and exact for long rows.
+    let chirp: Vec<Complex> = (0..n as u64)
+        .map(|k| Complex::cis(-PI * ((k * k) % (2 * n as u64)) as f64 / n as f64))
+        .collect();
+    let mut a = vec![Complex::default(); m];
+    let mut b = vec![Complex::default(); m];
+    for k in 0..n {
+        a[k] = data[k] * chirp[k];
+        b[k] = chirp[k].conj();
+        if k > 0 {
+            b[m - k] = chirp[k].conj();
+        }
+    }
+    fft(&mut a, false);
+    fft(&mut b, false);
+    for (a, b) in a.iter_mut().zip(&b) {
+        *a = *a * *b;
+    }
+    fft(&mut a, true);
+    let scale = Complex::new(1.0 / m as f64, 0.0);
+    for k in 0..n {
+        data[k] = a[k] * chirp[k] * scale;
+    }
+}
+
+// Iterative radix 2, the length a power of two. The inverse is left unscaled.
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let step = Complex::cis(sign * 2.0 * PI / len as f64);
+        for chunk in data.chunks_mut(len) {
+            let mut w = Complex::new(1.0, 0.0);
+            let (low, high) = chunk.split_at_mut(len / 2);
+            for (u, v) in low.iter_mut().zip(high.iter_mut()) {
+                let t = *v * w;
+                *v = *u - t;
+                *u = *u + t;
+                w = w * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Coord;
+    // straight from the definition, to check the fast paths against
+    fn naive(width: usize, height: usize, levels: &[f64]) -> Vec<f64> {
+        let mean = levels.iter().sum::<f64>() / levels.len() as f64;
+        let mut out = vec![0.0; width * height];
+        for v in 0..height {
+            for u in 0..width {
+                let mut sum = Complex::default();
+                for y in 0..height {
+                    for x in 0..width {
+                        let angle = -2.0
+                            * PI
+                            * ((u * x) as f64 / width as f64 + (v * y) as f64 / height as f64);
+                        sum = sum
+                            + Complex::cis(angle) * Complex::new(levels[y * width + x] - mean, 0.0);
+                    }
+                }
+                out[((v + height / 2) % height) * width + (u + width / 2) % width] = sum.norm();
+            }
+        }
+        out
+    }
+    #[test]
+    fn check_against_definition() {
+        for (width, height) in [(8, 4), (5, 3), (12, 7), (1, 6)] {
+            let levels: Vec<f64> = (0..width * height)
+                .map(|i| ((i * 7919) % 13) as f64 / 13.0)
+                .collect();
+            let spectrum = Spectrum::from_levels(width as u32, height as u32, levels.clone());
+            for (fast, slow) in spectrum
+                .magnitudes
+                .iter()
+                .zip(naive(width, height, &levels))
+            {
+                assert!(
+                    (fast - slow).abs() < 1e-9,
+                    "{width} x {height}: {fast} {slow}"
+                );
+            }
+        }
+    }
+    #[test]
+    fn check_stripes() {
+        // a cell in every 4 across 64 columns has only the frequencies 16, 32 and 48 across
+        let mask = Mask::from_fn(Coord::new(0, -20), Coord::new(63, 20), |v| {
+            crate::calc_coord::calc_coord_u64(v).x % 4 == 0
+        });
+        let spectrum = Spectrum::from_mask(&mask);
+        assert_eq!((spectrum.width, spectrum.height), (64, 41));
+        let peaks = spectrum.peaks(4);
+        let mut frequencies: Vec<(i32, i32)> = peaks[..3]
+            .iter()
+            .map(|(x, y, _)| spectrum.frequency_of(*x, *y))
+            .collect();
+        frequencies.sort();
+        assert_eq!(frequencies, vec![(-32, 0), (-16, 0), (16, 0)]);
+        assert!(peaks[3].2 < 1e-6);
+        // the mean is gone
+        assert!(spectrum.get(32, 20) < 1e-9);
+    }
+    #[test]
+    #[cfg(all(feature = "img", feature = "prime"))]
+    fn check_primes() {
+        let img = crate::ulamspiral_img::generate(45, 37).unwrap();
+        let spectrum = Spectrum::from_image(&img);
+        let mask = Mask::from_viewport(
+            &crate::viewport::Viewport::new(45, 37),
+            &crate::predicate::Primes::new(),
+        );
+        assert_eq!(spectrum, Spectrum::from_mask(&mask));
+        // a real input gives a spectrum that is the same turned half a turn about the middle
+        for y in 0..37 {
+            for x in 0..45 {
+                let turned = spectrum.get(44 - x, 36 - y);
+                assert!((spectrum.get(x, y) - turned).abs() < 1e-9);
+            }
+        }
+        let picture = spectrum.render();
+        assert_eq!(picture.dimensions(), (45, 37));
+        assert_eq!(picture.get_pixel(22, 18).0, [0]);
+        assert_eq!(picture.pixels().map(|p| p.0[0]).max(), Some(255));
+    }
+}
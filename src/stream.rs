@@ -0,0 +1,145 @@
+/// Writing spirals too big to hold in memory straight out as PNG, a band of rows at a time.
+use std::{error::Error, io::Write};
+
+use crate::{
+    predicate::{NumberPredicate, Primes},
+    value_of_coord_u64,
+    viewport::Viewport,
+};
+
+/// Cells worked out at once, a bit each, so memory use is about 16 MB plus one row of pixels.
+/// Taller bands cut the spiral into fewer, longer runs of values that are quicker to sieve.
+const BAND_CELLS: usize = 1 << 27;
+
+/// Same image as `ulamspiral_img::generate`, written to `writer` as it is drawn.
+/// # Examples
+/// ```no_run
+/// use std::{fs::File, io::BufWriter};
+/// // 40 gigapixels, far too big for `generate`
+/// let file = BufWriter::new(File::create("spiral.png").unwrap());
+/// ulam::stream::generate_png(200_000, 200_000, file).unwrap();
+/// ```
+pub fn generate_png<W: Write>(x_size: u32, y_size: u32, writer: W) -> Result<(), Box<dyn Error>> {
+    write_png(writer, &Viewport::new(x_size, y_size), &Primes::new())
+}
+
+/// Same image as `ulamspiral_img::render`, written to `writer` row by row without ever
+/// holding the whole image. Each band of rows is split into the runs of consecutive values
+/// the spiral makes across it, and the members of every run are listed in one go,
+/// which for `Primes` sieves that range of values with primes found once for the whole image.
+/// # Examples
+/// ```
+/// use ulam::{predicate::Primes, stream::write_png, ulamspiral_img::render, viewport::Viewport};
+/// let viewport = Viewport::new(300, 200).with_scale(2);
+/// let mut png = Vec::new();
+/// write_png(&mut png, &viewport, &Primes::new()).unwrap();
+/// let img = image::load_from_memory(&png).unwrap().into_luma8();
+/// assert_eq!(img, render(&viewport, &Primes::new()).unwrap());
+/// ```
+pub fn write_png<W, P>(writer: W, viewport: &Viewport, predicate: &P) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    P: NumberPredicate + ?Sized,
+{
+    if viewport.downsample > 1 {
+        return Err("streamed renders are not downsampled".into());
+    }
+    let scale = viewport.scale as usize;
+    let (width, height) = (viewport.pixel_width()?, viewport.pixel_height()?);
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    // the level `image` saves with, the default level is many times slower.
+    encoder.set_compression(png::Compression::Fast);
+    let mut png = encoder.write_header()?;
+    let mut stream = png.stream_writer()?;
+
+    let cols = viewport.width as usize;
+    let grid_lines = viewport.grid_lines && scale >= 2;
+    let mut marked = Vec::new();
+    let mut line = vec![0u8; width as usize];
+    let band_rows = (BAND_CELLS / cols.max(1)).max(1) as u32;
+    // set up once, so for `Primes` the sieving primes are shared by every run of every band.
+    let mut lister = predicate.range_lister(viewport.max_value());
+    for first in (0..viewport.height).step_by(band_rows as usize) {
+        let rows = first..first.saturating_add(band_rows).min(viewport.height);
+        let top_left = viewport.coord_at(0, first);
+        marked.clear();
+        marked.resize((cols * rows.len()).div_ceil(64), 0u64);
+        for (start, step, len) in viewport.arms(rows.clone()) {
+            let first_value = value_of_coord_u64(&start);
+            lister(first_value, first_value + len - 1, &mut |value| {
+                let along = (value - first_value) as i32;
+                let col = (start.x + step.0 * along - top_left.x) as usize;
+                let row = (top_left.y - start.y - step.1 * along) as usize;
+                let i = row * cols + col;
+                marked[i / 64] |= 1 << (i % 64);
+            });
+        }
+        for row in 0..rows.len() {
+            let last_row = rows.start as usize + row + 1 == viewport.height as usize;
+            for y in 0..scale {
+                if grid_lines && (y == 0 || last_row && y == scale - 1) {
+                    line.fill(64);
+                } else {
+                    for (col, pixels) in line.chunks_mut(scale).enumerate() {
+                        let i = row * cols + col;
+                        let cell = marked[i / 64] >> (i % 64) & 1 == 1;
+                        pixels.fill(if cell { 255 } else { 0 });
+                        if grid_lines {
+                            pixels[0] = 64;
+                        }
+                    }
+                    // the line closing the grid on the right
+                    if let (true, Some(last)) = (grid_lines, line.last_mut()) {
+                        *last = 64;
+                    }
+                }
+                stream.write_all(&line)?;
+            }
+        }
+    }
+    stream.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ulamspiral_img::render, Coord};
+    fn decode(png: &[u8]) -> image::GrayImage {
+        image::load_from_memory(png).unwrap().into_luma8()
+    }
+    #[test]
+    fn check_same_as_render() {
+        let primes = Primes::new();
+        for viewport in [
+            Viewport::new(1, 1),
+            Viewport::new(150, 130),
+            Viewport::new(40, 70).with_scale(3).with_grid_lines(true),
+            Viewport::centered_on(Coord::new(-5_000, 70), 90, 140),
+            Viewport::centered_on(Coord::new(30_000, -30_000), 64, 65),
+        ] {
+            let mut png = Vec::new();
+            write_png(&mut png, &viewport, &primes).unwrap();
+            assert_eq!(decode(&png), render(&viewport, &primes).unwrap());
+        }
+    }
+    #[test]
+    fn check_past_u32() {
+        // values up to about 1.6e10 in view, more than `generate` can number
+        let viewport = Viewport::centered_on(Coord::new(63_000, 0), 20, 200);
+        let odd = |n: u64| n % 2 == 1;
+        let mut png = Vec::new();
+        write_png(&mut png, &viewport, &odd).unwrap();
+        assert!(viewport.max_value() > u64::from(u32::MAX));
+        assert_eq!(decode(&png), render(&viewport, &odd).unwrap());
+        let mut png = Vec::new();
+        generate_png(10, 10, &mut png).unwrap();
+        assert_eq!(
+            decode(&png),
+            crate::ulamspiral_img::generate(10, 10).unwrap()
+        );
+        assert!(write_png(Vec::new(), &viewport.with_downsample(2), &odd).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+/// Vector output of the spiral as SVG, for small spirals that get scaled up in slides and docs.
+use std::{error::Error, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calc_coord::calc_coord_u64, mask::Mask, palette::Cell, palette::ColourMapper,
+    predicate::NumberPredicate, value_of_coord_u64, viewport::Viewport,
+};
+
+/// How a coloured cell is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Shape {
+    Rect,
+    Circle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SvgOptions {
+    pub shape: Shape,
+    /// Print the value of every cell in its middle.
+    pub numbers: bool,
+    /// Draw the spiral through the middle of the cells, from each value to the next.
+    pub path: bool,
+    /// Filled behind everything, `None` leaves the background transparent.
+    pub background: Option<[u8; 3]>,
+    /// Colour of the numbers, the path and the grid lines.
+    pub ink: [u8; 3],
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            shape: Shape::Rect,
+            numbers: false,
+            path: false,
+            background: Some([255, 255, 255]),
+            ink: [0, 0, 0],
+        }
+    }
+}
+
+/// Draw the viewport as an SVG document, `viewport.scale` user units per cell.
+/// Cells the mapper leaves transparent are not drawn.
+/// # Examples
+/// ```
+/// use ulam::{palette::Highlight, svg::{render, SvgOptions}, viewport::Viewport};
+/// let is_prime = |n: u64| n > 1 && (2..n).all(|d| n % d != 0);
+/// let viewport = Viewport::new(5, 5).with_scale(20);
+/// let options = SvgOptions { numbers: true, path: true, ..SvgOptions::default() };
+/// let svg = render(&viewport, &is_prime, &Highlight([0, 0, 0, 255]), &options).unwrap();
+/// assert!(svg.starts_with("<svg"));
+/// assert!(svg.contains(">23</text>"));
+/// ```
+pub fn render<P, M>(
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+    options: &SvgOptions,
+) -> Result<String, Box<dyn Error>>
+where
+    P: NumberPredicate + ?Sized,
+    M: ColourMapper + ?Sized,
+{
+    if viewport.downsample > 1 {
+        return Err("svg renders are not downsampled".into());
+    }
+    let scale = f64::from(viewport.scale.max(1));
+    let (width, height) = (
+        f64::from(viewport.width) * scale,
+        f64::from(viewport.height) * scale,
+    );
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+    if let Some(background) = options.background {
+        writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            hex(background)
+        )?;
+    }
+
+    let mask = Mask::from_viewport(viewport, predicate);
+    let mut values = Vec::with_capacity((viewport.width * viewport.height) as usize);
+    for row in 0..viewport.height {
+        for col in 0..viewport.width {
+            let coord = viewport.coord_at(col, row);
+            let value = value_of_coord_u64(&coord);
+            values.push(value);
+            let [r, g, b, a] = mapper.colour(&Cell::new(coord, value, mask.get(col, row)));
+            if a == 0 {
+                continue;
+            }
+            let (x, y) = (f64::from(col) * scale, f64::from(row) * scale);
+            let fill = hex([r, g, b]);
+            let opacity = if a == 255 {
+                String::new()
+            } else {
+                format!(r#" fill-opacity="{:.3}""#, f64::from(a) / 255.0)
+            };
+            match options.shape {
+                Shape::Rect => writeln!(
+                    svg,
+                    r#"<rect x="{x}" y="{y}" width="{scale}" height="{scale}" fill="{fill}"{opacity}/>"#
+                )?,
+                Shape::Circle => writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{}" fill="{fill}"{opacity}/>"#,
+                    x + scale / 2.0,
+                    y + scale / 2.0,
+                    scale / 2.0
+                )?,
+            }
+        }
+    }
+
+    let ink = hex(options.ink);
+    if viewport.grid_lines && viewport.scale >= 2 {
+        let mut d = String::new();
+        for col in 0..=viewport.width {
+            write!(d, "M{} 0V{height}", f64::from(col) * scale)?;
+        }
+        for row in 0..=viewport.height {
+            write!(d, "M0 {}H{width}", f64::from(row) * scale)?;
+        }
+        writeln!(
+            svg,
+            r#"<path d="{d}" fill="none" stroke="{ink}" stroke-opacity="0.25" stroke-width="1"/>"#
+        )?;
+    }
+
+    if options.path {
+        for run in path_runs(values.clone()) {
+            let points: Vec<String> = run
+                .iter()
+                .filter_map(|v| viewport.cell_of(&calc_coord_u64(*v)))
+                .map(|(col, row)| {
+                    format!(
+                        "{},{}",
+                        (f64::from(col) + 0.5) * scale,
+                        (f64::from(row) + 0.5) * scale
+                    )
+                })
+                .collect();
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{ink}" stroke-width="{}"/>"#,
+                points.join(" "),
+                scale / 10.0
+            )?;
+        }
+    }
+
+    if options.numbers {
+        // leave room for the widest number in view.
+        let digits = values.iter().max().map_or(1, |v| v.to_string().len());
+        let font_size = (scale / 2.0).min(scale * 1.6 / digits as f64);
+        for (i, value) in values.iter().enumerate() {
+            let (col, row) = (i as u32 % viewport.width, i as u32 / viewport.width);
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{font_size:.2}" fill="{ink}" text-anchor="middle" dominant-baseline="central">{value}</text>"#,
+                (f64::from(col) + 0.5) * scale,
+                (f64::from(row) + 0.5) * scale
+            )?;
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+// Split the values in view into runs of consecutive values, each run is one unbroken
+// stretch of the spiral. Runs of a single value have nothing to draw and are dropped.
+fn path_runs(mut values: Vec<u64>) -> Vec<Vec<u64>> {
+    values.sort_unstable();
+    let mut runs: Vec<Vec<u64>> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some(run) if run.last().map(|last| last + 1) == Some(value) => run.push(value),
+            _ => runs.push(vec![value]),
+        }
+    }
+    runs.retain(|run| run.len() > 1);
+    runs
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{palette::Highlight, Coord};
+    #[test]
+    fn check_cells() {
+        let viewport = Viewport::new(3, 3).with_scale(10);
+        let evens = |n: u64| n.is_multiple_of(2);
+        let options = SvgOptions {
+            background: None,
+            ..SvgOptions::default()
+        };
+        let svg = render(&viewport, &evens, &Highlight([255, 0, 0, 255]), &options).unwrap();
+        assert_eq!(svg.matches("<rect").count(), 5);
+        // 0 sits in the middle
+        assert!(svg.contains(r##"<rect x="10" y="10" width="10" height="10" fill="#ff0000"/>"##));
+        let options = SvgOptions {
+            shape: Shape::Circle,
+            ..options
+        };
+        let svg = render(&viewport, &evens, &Highlight([0, 0, 0, 128]), &options).unwrap();
+        assert_eq!(svg.matches("<circle").count(), 5);
+        assert!(svg.contains(r#"fill-opacity="0.502""#));
+    }
+    #[test]
+    fn check_path() {
+        assert_eq!(
+            path_runs(vec![7, 3, 4, 5, 9, 10]),
+            vec![vec![3, 4, 5], vec![9, 10]]
+        );
+        let options = SvgOptions {
+            path: true,
+            ..SvgOptions::default()
+        };
+        let svg = render(
+            &Viewport::new(3, 3),
+            &|_| false,
+            &Highlight([0; 4]),
+            &options,
+        )
+        .unwrap();
+        // the whole 3 x 3 spiral is one stretch, starting from 0 in the middle
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert!(svg.contains(r#"points="1.5,1.5 2.5,1.5 2.5,0.5"#));
+        // far from the center the view cuts the spiral into one stretch per column
+        let far = Viewport::centered_on(Coord::new(100, 0), 3, 3);
+        let svg = render(&far, &|_| false, &Highlight([0; 4]), &options).unwrap();
+        assert_eq!(svg.matches("<polyline").count(), 3);
+    }
+    #[test]
+    fn check_numbers() {
+        let options = SvgOptions {
+            numbers: true,
+            ..SvgOptions::default()
+        };
+        let viewport = Viewport::new(4, 4).with_scale(30).with_grid_lines(true);
+        let svg = render(&viewport, &|_| false, &Highlight([0; 4]), &options).unwrap();
+        assert_eq!(svg.matches("<text").count(), 16);
+        for value in 0..16 {
+            assert!(svg.contains(&format!(">{value}</text>")));
+        }
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert!(render(
+            &viewport.with_downsample(2),
+            &|_| false,
+            &Highlight([0; 4]),
+            &options
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,238 @@
+/// Drawing the spiral as text for a terminal, packing several cells into each character.
+use std::{error::Error, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mask::Mask,
+    palette::{over, Cell, ColourMapper, Highlight},
+    predicate::NumberPredicate,
+    value_of_coord_u64,
+    viewport::Viewport,
+};
+
+/// How cells are packed into characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Glyphs {
+    /// 2 x 4 cells per character, one braille dot each.
+    Braille,
+    /// 1 x 2 cells per character with the upper and lower half blocks.
+    HalfBlock,
+}
+
+impl Glyphs {
+    fn size(&self) -> (u32, u32) {
+        match self {
+            Glyphs::Braille => (2, 4),
+            Glyphs::HalfBlock => (1, 2),
+        }
+    }
+}
+
+// braille dot for each cell of a 2 x 4 block, indexed [row][col].
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// The highlighted cells of a viewport, without colours.
+/// `scale` has no meaning in a terminal and is ignored.
+/// # Examples
+/// ```
+/// use ulam::{terminal::{render, Glyphs}, viewport::Viewport};
+/// let is_prime = |n: u64| n > 1 && (2..n).all(|d| n % d != 0);
+/// let text = render(&Viewport::new(4, 4), &is_prime, Glyphs::HalfBlock).unwrap();
+/// assert_eq!(text, " ▄█▄\n▀▄  \n");
+/// ```
+pub fn render<P: NumberPredicate + ?Sized>(
+    viewport: &Viewport,
+    predicate: &P,
+    glyphs: Glyphs,
+) -> Result<String, Box<dyn Error>> {
+    draw(viewport, predicate, &Highlight([255; 4]), glyphs, false)
+}
+
+/// Like `render` with every cell the mapper colours drawn in 24-bit ANSI colour.
+/// Half blocks keep the exact colour of both cells. A braille character only has
+/// one colour, so its dots get the average of theirs.
+pub fn render_colour<P, M>(
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+    glyphs: Glyphs,
+) -> Result<String, Box<dyn Error>>
+where
+    P: NumberPredicate + ?Sized,
+    M: ColourMapper + ?Sized,
+{
+    draw(viewport, predicate, mapper, glyphs, true)
+}
+
+fn draw<P, M>(
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+    glyphs: Glyphs,
+    colour: bool,
+) -> Result<String, Box<dyn Error>>
+where
+    P: NumberPredicate + ?Sized,
+    M: ColourMapper + ?Sized,
+{
+    if viewport.downsample > 1 {
+        return Err("terminal renders are not downsampled".into());
+    }
+    let mask = Mask::from_viewport(viewport, predicate);
+    // the colour of a cell blended over black, `None` when it is not drawn.
+    let colour_at = |col: u32, row: u32| -> Option<[u8; 3]> {
+        if col >= viewport.width || row >= viewport.height {
+            return None;
+        }
+        let coord = viewport.coord_at(col, row);
+        let cell = Cell::new(coord, value_of_coord_u64(&coord), mask.get(col, row));
+        let colour = mapper.colour(&cell);
+        (colour[3] > 0).then(|| over(colour, [0; 3]))
+    };
+
+    let (glyph_width, glyph_height) = glyphs.size();
+    let mut text = String::new();
+    for y in (0..viewport.height).step_by(glyph_height as usize) {
+        let mut state = Ansi::default();
+        for x in (0..viewport.width).step_by(glyph_width as usize) {
+            let (glyph, fg, bg) = match glyphs {
+                Glyphs::Braille => {
+                    let mut bits = 0;
+                    let mut dots = Vec::new();
+                    for (dy, row) in BRAILLE_DOTS.iter().enumerate() {
+                        for (dx, dot) in row.iter().enumerate() {
+                            if let Some(c) = colour_at(x + dx as u32, y + dy as u32) {
+                                bits |= dot;
+                                dots.push(c);
+                            }
+                        }
+                    }
+                    let glyph = if bits == 0 {
+                        ' '
+                    } else {
+                        char::from_u32(0x2800 + bits).unwrap_or(' ')
+                    };
+                    (glyph, average(&dots), None)
+                }
+                Glyphs::HalfBlock => match (colour_at(x, y), colour_at(x, y + 1)) {
+                    (None, None) => (' ', None, None),
+                    (Some(top), None) => ('▀', Some(top), None),
+                    (None, Some(bottom)) => ('▄', Some(bottom), None),
+                    (Some(top), Some(bottom)) if !colour || top == bottom => ('█', Some(top), None),
+                    (Some(top), Some(bottom)) => ('▀', Some(top), Some(bottom)),
+                },
+            };
+            if colour {
+                state.switch(&mut text, fg, bg)?;
+            }
+            text.push(glyph);
+        }
+        state.reset(&mut text);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+fn average(colours: &[[u8; 3]]) -> Option<[u8; 3]> {
+    if colours.is_empty() {
+        return None;
+    }
+    let mut sum = [0usize; 3];
+    for c in colours {
+        for i in 0..3 {
+            sum[i] += usize::from(c[i]);
+        }
+    }
+    Some(sum.map(|s| (s / colours.len()) as u8))
+}
+
+// The colours the terminal is currently drawing with, so escapes are only written on a change.
+#[derive(Default)]
+struct Ansi {
+    fg: Option<[u8; 3]>,
+    bg: Option<[u8; 3]>,
+}
+
+impl Ansi {
+    fn switch(
+        &mut self,
+        text: &mut String,
+        fg: Option<[u8; 3]>,
+        bg: Option<[u8; 3]>,
+    ) -> Result<(), Box<dyn Error>> {
+        // blank glyphs look the same in any foreground colour.
+        let fg = fg.or(self.fg);
+        if (fg.is_none() && self.fg.is_some()) || (bg.is_none() && self.bg.is_some()) {
+            text.push_str("\x1b[0m");
+            *self = Ansi::default();
+        }
+        if let Some([r, g, b]) = fg.filter(|c| self.fg != Some(*c)) {
+            write!(text, "\x1b[38;2;{r};{g};{b}m")?;
+        }
+        if let Some([r, g, b]) = bg.filter(|c| self.bg != Some(*c)) {
+            write!(text, "\x1b[48;2;{r};{g};{b}m")?;
+        }
+        self.fg = fg;
+        self.bg = bg;
+        Ok(())
+    }
+
+    fn reset(&mut self, text: &mut String) {
+        if self.fg.is_some() || self.bg.is_some() {
+            text.push_str("\x1b[0m");
+        }
+        *self = Ansi::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_braille() {
+        let everything = |_| true;
+        let text = render(&Viewport::new(4, 8), &everything, Glyphs::Braille).unwrap();
+        assert_eq!(text, "⣿⣿\n⣿⣿\n");
+        // cut short at the edges
+        let text = render(&Viewport::new(3, 5), &everything, Glyphs::Braille).unwrap();
+        assert_eq!(text, "⣿⡇\n⠉⠁\n");
+        let text = render(&Viewport::new(2, 4), &|n| n == 0, Glyphs::Braille).unwrap();
+        // 0 sits in the left column, third row
+        assert_eq!(text, "⠄\n");
+    }
+    #[test]
+    fn check_half_block() {
+        let text = render(
+            &Viewport::new(3, 3),
+            &|n: u64| !n.is_multiple_of(2),
+            Glyphs::HalfBlock,
+        )
+        .unwrap();
+        // 3 on the top row, 5 and 1 on the middle row, 7 on the bottom
+        assert_eq!(text, "▄▀▄\n ▀ \n");
+    }
+    #[test]
+    fn check_colour() {
+        let red = |cell: &Cell| [255, 0, 0, if cell.value == 0 { 0 } else { 255 }];
+        let text =
+            render_colour(&Viewport::new(2, 2), &|_| false, &red, Glyphs::HalfBlock).unwrap();
+        assert_eq!(text, "\x1b[38;2;255;0;0m▀█\x1b[0m\n");
+        let split = |cell: &Cell| {
+            if cell.coord.y > 0 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            }
+        };
+        let text =
+            render_colour(&Viewport::new(1, 2), &|_| false, &split, Glyphs::HalfBlock).unwrap();
+        assert_eq!(text, "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\n");
+        assert!(render(
+            &Viewport::new(4, 4).with_downsample(2),
+            &|_| true,
+            Glyphs::Braille
+        )
+        .is_err());
+    }
+}
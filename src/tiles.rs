@@ -0,0 +1,328 @@
+/// Cutting a spiral into the z/x/y pyramid of 256 pixel PNG tiles that web map viewers load.
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    density::{DensityGrid, ToneMap},
+    predicate::NumberPredicate,
+    ulamspiral_img::{render, GreyImage},
+    viewport::Viewport,
+};
+
+pub const TILE_SIZE: u32 = 256;
+
+/// A pyramid of tiles for the spiral out to `radius` cells from 0.
+///
+/// At zoom 0 one tile shows the whole spiral. Each zoom level doubles the size, and from
+/// `native_zoom` on every cell is at least one pixel and tiles show exact cells.
+/// Shallower levels are drawn from the density of each block of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tiler {
+    pub radius: u32,
+    pub max_zoom: u32,
+    pub tone: ToneMap,
+    /// The density drawn white on the shallow levels. It is fixed for the whole pyramid
+    /// so neighbouring tiles share one scale.
+    pub density_max: f64,
+}
+
+/// What `Tiler::generate` did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TileCount {
+    pub written: u64,
+    /// Tiles already on disk from an earlier run.
+    pub skipped: u64,
+}
+
+impl Tiler {
+    /// Tiles down to 4 pixels per cell, with a log tone map on the shallow levels.
+    /// Radii from 2^29 on are refused.
+    /// # Examples
+    /// ```
+    /// use ulam::tiles::Tiler;
+    /// let tiler = Tiler::new(1_000).unwrap();
+    /// // 2,001 cells across fit in 2,048, which is 8 tiles at 1 pixel per cell
+    /// assert_eq!(tiler.world_size(), 2_048);
+    /// assert_eq!(tiler.native_zoom(), 3);
+    /// assert_eq!(tiler.max_zoom, 5);
+    /// ```
+    pub fn new(radius: u32) -> Result<Tiler, Box<dyn Error>> {
+        if radius >= 1 << 29 {
+            return Err("radius too large to tile".into());
+        }
+        let tiler = Tiler {
+            radius,
+            max_zoom: 0,
+            tone: ToneMap::Log,
+            density_max: 1.0,
+        };
+        Ok(Tiler {
+            max_zoom: tiler.native_zoom() + 2,
+            ..tiler
+        })
+    }
+
+    /// Zoom levels past `deepest_zoom` are left out.
+    pub fn with_max_zoom(self, max_zoom: u32) -> Tiler {
+        Tiler {
+            max_zoom: max_zoom.min(self.deepest_zoom()),
+            ..self
+        }
+    }
+
+    pub fn with_tone(self, tone: ToneMap, density_max: f64) -> Tiler {
+        Tiler {
+            tone,
+            density_max,
+            ..self
+        }
+    }
+
+    /// Cells across the whole pyramid, a power of two so every zoom level divides evenly.
+    pub fn world_size(&self) -> u32 {
+        (2 * self.radius + 1).next_power_of_two().max(TILE_SIZE)
+    }
+
+    /// The first zoom level with at least one pixel per cell.
+    pub fn native_zoom(&self) -> u32 {
+        (self.world_size() / TILE_SIZE).trailing_zeros()
+    }
+
+    /// The last zoom level, where one cell fills a whole tile.
+    pub fn deepest_zoom(&self) -> u32 {
+        self.native_zoom() + TILE_SIZE.trailing_zeros()
+    }
+
+    /// The cells a tile shows. Tiles past the native zoom are scaled up,
+    /// tiles before it are downsampled.
+    ///
+    /// # Panics
+    /// If `z` is past `deepest_zoom`.
+    pub fn viewport(&self, z: u32, x: u32, y: u32) -> Viewport {
+        assert!(
+            z <= self.deepest_zoom(),
+            "zoom {z} is past the deepest level"
+        );
+        let world = Viewport::new(self.world_size(), self.world_size());
+        let cells = self.world_size() >> z;
+        let viewport = Viewport::from_top_left(world.coord_at(x * cells, y * cells), cells, cells);
+        if cells >= TILE_SIZE {
+            viewport.with_downsample(cells / TILE_SIZE)
+        } else {
+            viewport.with_scale(TILE_SIZE / cells)
+        }
+    }
+
+    /// Whether a tile shows any of the spiral.
+    pub fn in_spiral(&self, z: u32, x: u32, y: u32) -> bool {
+        if z > self.deepest_zoom() {
+            return false;
+        }
+        let tiles = 1u64 << z;
+        if u64::from(x) >= tiles || u64::from(y) >= tiles {
+            return false;
+        }
+        let viewport = self.viewport(z, x, y);
+        let (min, max) = (viewport.min(), viewport.max());
+        let radius = self.radius as i32;
+        min.x <= radius && max.x >= -radius && min.y <= radius && max.y >= -radius
+    }
+
+    /// Draw one tile, values in the predicate are white and cells past the radius stay black.
+    pub fn render_tile<P: NumberPredicate + ?Sized>(
+        &self,
+        z: u32,
+        x: u32,
+        y: u32,
+        predicate: &P,
+    ) -> Result<GreyImage, Box<dyn Error>> {
+        if z > self.deepest_zoom() {
+            return Err(format!("zoom {z} is past the deepest level").into());
+        }
+        let viewport = self.viewport(z, x, y);
+        let predicate = Within {
+            predicate,
+            max_value: (2 * u64::from(self.radius) + 1).pow(2) - 1,
+        };
+        if viewport.downsample == 1 {
+            return render(&viewport, &predicate);
+        }
+        let grid = DensityGrid::new(&viewport, &predicate);
+        let levels = grid.tone_up_to(self.tone, self.density_max);
+        GreyImage::from_raw(grid.width, grid.height, levels).ok_or_else(|| "bad tile size".into())
+    }
+
+    /// Render one tile to `dir/z/x/y.png`, under a temporary name first so a half written
+    /// tile is never left behind.
+    pub fn write_tile<P: NumberPredicate + ?Sized>(
+        &self,
+        dir: &Path,
+        z: u32,
+        x: u32,
+        y: u32,
+        predicate: &P,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let path = tile_path(dir, z, x, y);
+        fs::create_dir_all(path.parent().ok_or("bad tile path")?)?;
+        let tmp = path.with_extension("png.tmp");
+        self.render_tile(z, x, y, predicate)?
+            .save_with_format(&tmp, image::ImageFormat::Png)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Write every tile of the spiral to `dir/z/x/y.png`, from zoom 0 to `max_zoom`.
+    /// Tiles already there are kept, so an interrupted run picks up where it stopped.
+    pub fn generate<P: NumberPredicate + ?Sized>(
+        &self,
+        dir: &Path,
+        predicate: &P,
+    ) -> Result<TileCount, Box<dyn Error>> {
+        let mut count = TileCount::default();
+        for z in 0..=self.max_zoom {
+            for x in 0..1u32 << z {
+                for y in 0..1u32 << z {
+                    if !self.in_spiral(z, x, y) {
+                        continue;
+                    }
+                    let path = tile_path(dir, z, x, y);
+                    if path.exists() {
+                        count.skipped += 1;
+                        continue;
+                    }
+                    self.write_tile(dir, z, x, y, predicate)?;
+                    count.written += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+pub fn tile_path(dir: &Path, z: u32, x: u32, y: u32) -> PathBuf {
+    dir.join(z.to_string())
+        .join(x.to_string())
+        .join(format!("{y}.png"))
+}
+
+// A predicate cut off past the edge of the spiral.
+struct Within<'a, P: ?Sized> {
+    predicate: &'a P,
+    max_value: u64,
+}
+
+impl<P: NumberPredicate + ?Sized> NumberPredicate for Within<'_, P> {
+    fn contains(&self, n: u64) -> bool {
+        n <= self.max_value && self.predicate.contains(n)
+    }
+
+    fn members(&self, start: u64, end: u64) -> Box<dyn Iterator<Item = u64> + '_> {
+        self.predicate.members(start, end.min(self.max_value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{predicate::Primes, ulamspiral_img::generate, Coord};
+    #[test]
+    fn check_viewports() {
+        let tiler = Tiler::new(300).unwrap();
+        assert_eq!(tiler.world_size(), 1024);
+        assert_eq!(tiler.native_zoom(), 2);
+        let top = tiler.viewport(0, 0, 0);
+        assert_eq!((top.width, top.downsample, top.scale), (1024, 4, 1));
+        assert_eq!(top.pixel_width().unwrap(), TILE_SIZE);
+        let deep = tiler.viewport(4, 3, 5);
+        assert_eq!((deep.width, deep.downsample, deep.scale), (64, 1, 4));
+        assert_eq!(deep.pixel_width().unwrap(), TILE_SIZE);
+        // tiles line up with each other
+        assert_eq!(
+            tiler.viewport(4, 4, 5).top_left(),
+            Coord::new(deep.top_left().x + 64, deep.top_left().y)
+        );
+        assert_eq!(
+            tiler.viewport(2, 0, 0).top_left(),
+            tiler.viewport(0, 0, 0).top_left()
+        );
+        assert!(tiler.in_spiral(2, 1, 1));
+        assert!(!tiler.in_spiral(4, 0, 0));
+        assert!(!tiler.in_spiral(1, 2, 0));
+
+        let largest = Tiler::new((1 << 29) - 1).unwrap();
+        assert_eq!(largest.world_size(), 1 << 30);
+        assert!(Tiler::new(1 << 29).is_err());
+        assert!(Tiler::new(u32::MAX).is_err());
+    }
+    #[test]
+    fn check_deepest_zoom() {
+        let tiler = Tiler::new(300).unwrap();
+        assert_eq!(tiler.deepest_zoom(), 10);
+        assert_eq!(tiler.with_max_zoom(40).max_zoom, 10);
+        // one cell per tile at the limit, and every tile a different cell
+        let deepest = tiler.viewport(10, 511, 512);
+        assert_eq!((deepest.width, deepest.scale), (1, TILE_SIZE));
+        assert_eq!(deepest.pixel_width().unwrap(), TILE_SIZE);
+        assert_eq!(deepest.top_left(), Coord::new(0, 0));
+        assert_eq!(tiler.viewport(10, 512, 512).top_left(), Coord::new(1, 0));
+        // 0 is black, 3 just above it is white
+        let tile = tiler.render_tile(10, 511, 512, &Primes::new()).unwrap();
+        assert!(tile.pixels().all(|p| p.0 == [0]));
+        let tile = tiler.render_tile(10, 511, 511, &Primes::new()).unwrap();
+        assert!(tile.pixels().all(|p| p.0 == [255]));
+        assert!(tiler.in_spiral(10, 511, 512));
+        assert!(!tiler.in_spiral(11, 1024, 1024));
+        assert!(tiler.render_tile(11, 1024, 1024, &Primes::new()).is_err());
+    }
+    #[test]
+    fn check_native_tile() {
+        // at the native zoom the middle tiles are pieces of the plain image
+        let tiler = Tiler::new(255).unwrap();
+        assert_eq!((tiler.world_size(), tiler.native_zoom()), (512, 1));
+        let whole = generate(512, 512).unwrap();
+        let primes = Primes::up_to(600_000);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let tile = tiler.render_tile(1, x, y, &primes).unwrap();
+            for (px, py, pixel) in tile.enumerate_pixels() {
+                let (wx, wy) = (x * TILE_SIZE + px, y * TILE_SIZE + py);
+                // the last column and the top row are past the radius
+                let inside = wx < 511 && wy > 0;
+                if inside {
+                    assert_eq!(pixel, whole.get_pixel(wx, wy), "{wx}, {wy}");
+                }
+            }
+        }
+    }
+    #[test]
+    fn check_generate() {
+        let dir = std::env::temp_dir().join(format!("ulam-tiles-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let tiler = Tiler::new(100).unwrap().with_max_zoom(2);
+        let primes = Primes::new();
+        let first = tiler.generate(&dir, &primes).unwrap();
+        assert_eq!(
+            first,
+            TileCount {
+                written: 1 + 4 + 16,
+                skipped: 0
+            }
+        );
+        fs::remove_file(tile_path(&dir, 2, 1, 2)).unwrap();
+        let second = tiler.generate(&dir, &primes).unwrap();
+        assert_eq!(
+            second,
+            TileCount {
+                written: 1,
+                skipped: 20
+            }
+        );
+        let tile = image::open(tile_path(&dir, 2, 1, 2)).unwrap();
+        assert_eq!((tile.width(), tile.height()), (TILE_SIZE, TILE_SIZE));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,66 +1,220 @@
-use std::{cmp, error::Error};
+use std::error::Error;
+
+use crate::{
+    calc_coord::calc_coord_u64,
+    cramer::CramerModel,
+    density::{DensityGrid, ToneMap},
+    mask::Mask,
+    palette::{over, Cell, ColourMapper, ModSix},
+    predicate::{NumberPredicate, Primes},
+    value_of_coord_u64,
+    viewport::Viewport,
+};
 
 pub type GreyImage = image::GrayImage; // because ben is british
 
 pub fn generate(x_size: u32, y_size: u32) -> Result<GreyImage, Box<dyn Error>> {
-    let mut img = image::ImageBuffer::new(x_size, y_size);
-    let total = cmp::max(x_size, y_size).pow(2).try_into()?;
-    let sieve = primal::Sieve::new(total);
-    let pixel = image::Luma::from([255]);
+    let viewport = Viewport::new(x_size, y_size);
+    render(&viewport, &Primes::up_to(viewport.max_value()))
+}
+
+pub fn generate_colour(
+    // because Ben is british color is spelled colour.
+    x_size: u32,
+    y_size: u32,
+) -> Result<image::RgbImage, Box<dyn Error>> {
+    let viewport = Viewport::new(x_size, y_size);
+    render_colour(&viewport, &Primes::up_to(viewport.max_value()), &ModSix)
+}
 
-    for prime in sieve.primes_from(0).take_while(|x| *x <= total) {
-        let coord = crate::calc_coord::calc_coord(prime.try_into()?);
-        let pos_x: i64 = i64::from(x_size) - i64::from(x_size) / 2 + i64::from(coord.x);
-        let pos_y: i64 = i64::from(y_size) / 2 - i64::from(coord.y) + 1;
-        if pos_x < 1
-            || u32::try_from(pos_x)? > x_size
-            || pos_y < 1
-            || u32::try_from(pos_y)? > y_size
-        {
-            // A rectangle is being generated and this prime lies outside it.
-            continue;
+// const RED: [u8; 3] = [255, 0, 0];
+// const GREEN: [u8; 3] = [0, 255, 0];
+// const BLUE: [u8; 3] = [0, 0, 255];
+
+/// Draw a viewport of the spiral with the values in the predicate in white.
+/// Downsampled viewports are drawn the same as `render_density` with a linear tone map.
+/// # Examples
+/// ```
+/// use ulam::{predicate::Primes, ulamspiral_img::render, viewport::Viewport, Coord};
+/// let viewport = Viewport::centered_on(Coord::new(100, 50), 40, 30)
+///     .with_scale(4)
+///     .with_grid_lines(true);
+/// let img = render(&viewport, &Primes::new()).unwrap();
+/// assert_eq!(img.dimensions(), (160, 120));
+/// ```
+pub fn render<P: NumberPredicate + ?Sized>(
+    viewport: &Viewport,
+    predicate: &P,
+) -> Result<GreyImage, Box<dyn Error>> {
+    if viewport.downsample > 1 {
+        return render_density(viewport, predicate, ToneMap::Linear);
+    }
+    paint(viewport, predicate)
+}
+
+/// Draw a downsampled viewport, each pixel as bright as the share of its block in the predicate.
+/// # Examples
+/// ```
+/// use ulam::{density::ToneMap, predicate::Primes, ulamspiral_img::render_density, viewport::Viewport};
+/// // 2,000 x 2,000 cells, 10 x 10 cells to a pixel
+/// let viewport = Viewport::new(2_000, 2_000).with_downsample(10);
+/// let img = render_density(&viewport, &Primes::new(), ToneMap::Log).unwrap();
+/// assert_eq!(img.dimensions(), (200, 200));
+/// ```
+pub fn render_density<P: NumberPredicate + ?Sized>(
+    viewport: &Viewport,
+    predicate: &P,
+    tone: ToneMap,
+) -> Result<GreyImage, Box<dyn Error>> {
+    let scale = viewport.scale;
+    let (width, height) = (viewport.pixel_width()?, viewport.pixel_height()?);
+    let grid = DensityGrid::new(viewport, predicate);
+    let levels = grid.tone(tone);
+    Ok(image::ImageBuffer::from_fn(width, height, |x, y| {
+        image::Luma([levels[((y / scale) * grid.width + x / scale) as usize]])
+    }))
+}
+
+/// Draw a viewport of the spiral with every cell coloured by a `ColourMapper`.
+/// Cells the mapper leaves partly transparent are blended over black.
+/// # Examples
+/// ```
+/// use ulam::{palette::ByResidue, predicate::Primes, ulamspiral_img::render_colour, viewport::Viewport};
+/// let viewport = Viewport::new(200, 200).with_scale(2);
+/// let img = render_colour(&viewport, &Primes::new(), &ByResidue::new(10)).unwrap();
+/// assert_eq!(img.dimensions(), (400, 400));
+/// ```
+pub fn render_colour<P, M>(
+    viewport: &Viewport,
+    predicate: &P,
+    mapper: &M,
+) -> Result<image::RgbImage, Box<dyn Error>>
+where
+    P: NumberPredicate + ?Sized,
+    M: ColourMapper + ?Sized,
+{
+    if viewport.downsample > 1 {
+        return Err("colour renders are not downsampled, use render_density".into());
+    }
+    let mut img = canvas(viewport)?;
+    let mask = Mask::from_viewport(viewport, predicate);
+
+    for row in 0..viewport.height {
+        for col in 0..viewport.width {
+            let coord = viewport.coord_at(col, row);
+            let cell = Cell::new(coord, value_of_coord_u64(&coord), mask.get(col, row));
+            let colour = mapper.colour(&cell);
+            if colour[3] == 0 {
+                continue;
+            }
+            let pixel = image::Rgb(over(colour, [0; 3]));
+            fill(&mut img, viewport.scale, col, row, pixel);
         }
-        img.put_pixel(u32::try_from(pos_x)? - 1, u32::try_from(pos_y)? - 1, pixel);
     }
 
+    draw_grid(&mut img, viewport, image::Rgb([64, 64, 64]));
     Ok(img)
 }
 
-// const RED: [u8; 3] = [255, 0, 0];
-// const GREEN: [u8; 3] = [0, 255, 0];
-// const BLUE: [u8; 3] = [0, 0, 255];
+pub(crate) type Canvas<P> = image::ImageBuffer<P, Vec<<P as image::Pixel>::Subpixel>>;
 
-pub fn generate_colour(
-    // because Ben is british color is spelled colour.
+// An empty image the size of the viewport.
+pub(crate) fn canvas<Px: image::Pixel>(viewport: &Viewport) -> Result<Canvas<Px>, Box<dyn Error>> {
+    Ok(image::ImageBuffer::new(
+        viewport.pixel_width()?,
+        viewport.pixel_height()?,
+    ))
+}
+
+pub(crate) fn fill<Px: image::Pixel>(
+    img: &mut Canvas<Px>,
+    scale: u32,
+    col: u32,
+    row: u32,
+    pixel: Px,
+) {
+    for y in row * scale..(row + 1) * scale {
+        for x in col * scale..(col + 1) * scale {
+            img.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+pub(crate) fn draw_grid<Px: image::Pixel>(img: &mut Canvas<Px>, viewport: &Viewport, grid: Px) {
+    if viewport.grid_lines {
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            if viewport.on_grid_line(x, y) {
+                *pixel = grid;
+            }
+        }
+    }
+}
+
+// Fill in every cell of the viewport whose value is in the predicate.
+fn paint<P>(viewport: &Viewport, predicate: &P) -> Result<GreyImage, Box<dyn Error>>
+where
+    P: NumberPredicate + ?Sized,
+{
+    let mut img = canvas(viewport)?;
+    let pixel = image::Luma::from([255]);
+
+    if viewport.lists_members() {
+        for value in predicate.members(0, viewport.max_value()) {
+            let coord = calc_coord_u64(value);
+            if let Some((col, row)) = viewport.cell_of(&coord) {
+                fill(&mut img, viewport.scale, col, row, pixel);
+            }
+        }
+    } else {
+        for row in 0..viewport.height {
+            for col in 0..viewport.width {
+                let value = value_of_coord_u64(&viewport.coord_at(col, row));
+                if predicate.contains(value) {
+                    fill(&mut img, viewport.scale, col, row, pixel);
+                }
+            }
+        }
+    }
+
+    draw_grid(&mut img, viewport, image::Luma::from([64]));
+    Ok(img)
+}
+
+/// Same as `generate` but marks the values in any predicate instead of the primes.
+/// # Examples
+/// ```
+/// use ulam::{predicate::PerfectSquares, ulamspiral_img::generate_with};
+/// let squares = generate_with(100, 100, &PerfectSquares).unwrap();
+/// let odd = generate_with(100, 100, &|n: u64| n % 2 == 1).unwrap();
+/// ```
+pub fn generate_with<P: NumberPredicate + ?Sized>(
     x_size: u32,
     y_size: u32,
-) -> Result<image::RgbImage, Box<dyn Error>> {
-    let mut img = image::RgbImage::new(x_size, y_size);
-    let total = cmp::max(x_size, y_size).pow(2).try_into()?;
-    let sieve = primal::Sieve::new(total);
-
-    for prime in sieve.primes_from(0).take_while(|x| *x <= total) {
-        let coord = crate::calc_coord::calc_coord(prime.try_into()?);
-        let pos_x: i64 = i64::from(x_size) - i64::from(x_size) / 2 + i64::from(coord.x);
-        let pos_y: i64 = i64::from(y_size) / 2 - i64::from(coord.y) + 1;
-        if pos_x < 1
-            || u32::try_from(pos_x)? > x_size
-            || pos_y < 1
-            || u32::try_from(pos_y)? > y_size
-        {
-            // A rectangle is being generated and this prime lies outside it.
-            continue;
-        }
+    predicate: &P,
+) -> Result<GreyImage, Box<dyn Error>> {
+    render(&Viewport::new(x_size, y_size), predicate)
+}
 
-        let pixel = image::Rgb(match prime % 6 {
-            0 => [255, 0, 0],
-            1 => [0, 255, 0],
-            3 => [255, 0, 0],
-            5 => [0, 0, 255],
-            _ => [255, 255, 255],
-        });
+/// Same as `generate` but marks the pseudo-primes of Cramér's random model instead,
+/// so the real spiral can be compared with a random one.
+pub fn generate_cramer(x_size: u32, y_size: u32, seed: u64) -> Result<GreyImage, Box<dyn Error>> {
+    generate_with(x_size, y_size, &CramerModel::new(seed))
+}
 
-        img.put_pixel(u32::try_from(pos_x)? - 1, u32::try_from(pos_y)? - 1, pixel);
+/// The real primes on the left and Cramér's random model on the right,
+/// split by a grey column.
+pub fn generate_side_by_side(
+    x_size: u32,
+    y_size: u32,
+    seed: u64,
+) -> Result<GreyImage, Box<dyn Error>> {
+    let real = generate(x_size, y_size)?;
+    let random = generate_cramer(x_size, y_size, seed)?;
+    let mut img = image::ImageBuffer::new(2 * x_size + 1, y_size);
+    image::imageops::replace(&mut img, &real, 0, 0);
+    image::imageops::replace(&mut img, &random, i64::from(x_size) + 1, 0);
+    for y in 0..y_size {
+        img.put_pixel(x_size, y, image::Luma::from([128]));
     }
 
     Ok(img)
@@ -131,4 +285,141 @@ mod tests {
         expected_wide.put_pixel(2, 4, image::Luma::from([255_u8]));
         assert_eq!(wide, expected_wide);
     }
+    #[test]
+    fn check_cramer() {
+        let img = generate_cramer(41, 41, 9).unwrap();
+        let mask = CramerModel::new(9).mask(crate::Coord::new(-20, -20), crate::Coord::new(20, 20));
+        for (i, marked) in mask.cells.iter().enumerate() {
+            let p = img.get_pixel(i as u32 % 41, i as u32 / 41);
+            assert_eq!(*marked, p[0] == 255);
+        }
+    }
+    #[test]
+    fn check_side_by_side() {
+        let img = generate_side_by_side(5, 5, 1).unwrap();
+        assert_eq!(img.dimensions(), (11, 5));
+        assert_eq!(img.get_pixel(5, 2), &image::Luma([128]));
+        assert_eq!(img.get_pixel(3, 0), &image::Luma([255]));
+    }
+    #[test]
+    fn check_with() {
+        let primes = crate::predicate::Primes::new();
+        assert_eq!(
+            generate_with(5, 5, &primes).unwrap(),
+            generate(5, 5).unwrap()
+        );
+        assert_eq!(
+            generate_with(5, 3, &primes).unwrap(),
+            generate(5, 3).unwrap()
+        );
+        let squares = generate_with(3, 3, &crate::predicate::PerfectSquares).unwrap();
+        // 0 1 and 4
+        assert_eq!(squares.pixels().filter(|p| p[0] == 255).count(), 3);
+    }
+    #[test]
+    fn check_colour() {
+        let img = generate_colour(5, 5).unwrap();
+        // 2 and 3 are the only primes that are not 1 or 5 mod 6
+        assert_eq!(img.get_pixel(3, 1), &image::Rgb([255, 255, 255]));
+        assert_eq!(img.get_pixel(2, 1), &image::Rgb([255, 0, 0]));
+        assert_eq!(img.get_pixel(4, 1), &image::Rgb([0, 0, 255]));
+        assert_eq!(img.get_pixel(2, 2), &image::Rgb([0, 0, 0]));
+    }
+    #[test]
+    fn check_mapper() {
+        use crate::palette::{ByQuad, TRANSPARENT};
+        let viewport = Viewport::new(5, 5);
+        let img = render_colour(&viewport, &Primes::new(), &ByQuad::new()).unwrap();
+        let grey = generate(5, 5).unwrap();
+        for (x, y, pixel) in img.enumerate_pixels() {
+            assert_eq!(
+                pixel != &image::Rgb([0, 0, 0]),
+                grey.get_pixel(x, y)[0] == 255
+            );
+        }
+        // composites, half transparent
+        let half = |c: &Cell| {
+            if c.highlighted {
+                TRANSPARENT
+            } else {
+                [200, 100, 0, 128]
+            }
+        };
+        let img = render_colour(&viewport, &Primes::new(), &half).unwrap();
+        assert_eq!(img.get_pixel(2, 2), &image::Rgb([100, 50, 0]));
+        assert_eq!(img.get_pixel(3, 1), &image::Rgb([0, 0, 0]));
+        assert!(render_colour(&viewport.with_downsample(2), &Primes::new(), &half).is_err());
+    }
+    #[test]
+    fn check_viewport() {
+        let odd = generate(5, 5).unwrap();
+        let same = render(&Viewport::new(5, 5), &Primes::new()).unwrap();
+        assert_eq!(odd, same);
+
+        // the 5x5 fixture again, moved one cell right and scaled up
+        let viewport = Viewport::centered_on(crate::Coord::new(1, 0), 5, 5).with_scale(3);
+        let scaled = render(&viewport, &Primes::new()).unwrap();
+        assert_eq!(scaled.dimensions(), (15, 15));
+        for (x, y, pixel) in scaled.enumerate_pixels() {
+            if x / 3 < 4 {
+                assert_eq!(pixel, odd.get_pixel(x / 3 + 1, y / 3));
+            }
+        }
+    }
+    #[test]
+    fn check_grid_lines() {
+        let viewport = Viewport::new(3, 3).with_scale(4).with_grid_lines(true);
+        let img = render(&viewport, &Primes::new()).unwrap();
+        assert_eq!(img.get_pixel(4, 5), &image::Luma([64]));
+        assert_eq!(img.get_pixel(5, 4), &image::Luma([64]));
+        // 3 is above the center
+        assert_eq!(img.get_pixel(5, 1), &image::Luma([255]));
+        assert_eq!(img.get_pixel(5, 5), &image::Luma([0]));
+        // the grid is closed on the right and at the bottom
+        assert_eq!(img.get_pixel(11, 5), &image::Luma([64]));
+        assert_eq!(img.get_pixel(5, 11), &image::Luma([64]));
+        assert_eq!(img.get_pixel(10, 10), &image::Luma([0]));
+        // no room for grid lines at one pixel per cell
+        let flat = render(&viewport.with_scale(1), &Primes::new()).unwrap();
+        assert_eq!(flat, generate(3, 3).unwrap());
+    }
+    #[test]
+    fn check_density() {
+        // one pixel per cell is the plain render
+        let viewport = Viewport::new(9, 9);
+        let dense = render_density(&viewport, &Primes::new(), ToneMap::Linear).unwrap();
+        assert_eq!(dense, generate(9, 9).unwrap());
+
+        let viewport = Viewport::new(9, 9).with_downsample(3).with_scale(2);
+        let img = render(&viewport, &Primes::new()).unwrap();
+        assert_eq!(img.dimensions(), (6, 6));
+        let grid = DensityGrid::new(&viewport, &Primes::new());
+        // the middle block holds 2 3 5 7
+        assert_eq!(grid.hits[4], 4);
+        let brightest = grid.hits.iter().max().unwrap();
+        assert_eq!(img.get_pixel(2, 2)[0], (4 * 255 / brightest) as u8);
+        assert_eq!(img.get_pixel(3, 3), img.get_pixel(2, 2));
+    }
+    #[test]
+    fn check_far_out() {
+        // past where u32 values run out
+        let viewport = Viewport::centered_on(crate::Coord::new(40_000, 0), 20, 20);
+        let img = render(&viewport, &Primes::new()).unwrap();
+        let value = value_of_coord_u64(&viewport.coord_at(3, 4));
+        assert!(value > u64::from(u32::MAX));
+        assert_eq!(img.get_pixel(3, 4)[0] == 255, primal::is_prime(value));
+    }
+    #[test]
+    fn check_far_viewport() {
+        // far enough out that every cell gets tested on its own
+        let viewport = Viewport::centered_on(crate::Coord::new(300, -200), 7, 5);
+        let img = render(&viewport, &Primes::new()).unwrap();
+        for row in 0..5 {
+            for col in 0..7 {
+                let value = crate::value_of_coord(&viewport.coord_at(col, row));
+                let lit = img.get_pixel(col, row)[0] == 255;
+                assert_eq!(lit, primal::is_prime(value.into()));
+            }
+        }
+    }
 }
@@ -0,0 +1,297 @@
+/// Which part of the spiral gets rendered, and how big each cell is drawn.
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Coord;
+
+/// A window of `width` by `height` cells around `center`, drawn `scale` pixels per cell.
+/// With a `downsample` above 1 each pixel stands for a square block of that many cells a side instead.
+///
+/// When the width or height is even the center sits just left of or below the middle,
+/// the same as the images from `ulamspiral_img::generate` have always done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub center: Coord,
+    pub width: u32,
+    pub height: u32,
+    pub scale: u32,
+    pub downsample: u32,
+    /// Outline every cell, only drawn when `scale` is at least 2.
+    pub grid_lines: bool,
+}
+
+impl Viewport {
+    /// A viewport centered on 0 at one pixel per cell.
+    /// # Examples
+    /// ```
+    /// use ulam::{viewport::Viewport, Coord};
+    /// let v = Viewport::new(5, 4);
+    /// assert_eq!(v.top_left(), Coord::new(-2, 2));
+    /// assert_eq!(v.coord_at(2, 2), Coord::new(0, 0));
+    /// ```
+    pub fn new(width: u32, height: u32) -> Viewport {
+        Viewport::centered_on(Coord::new(0, 0), width, height)
+    }
+
+    pub fn centered_on(center: Coord, width: u32, height: u32) -> Viewport {
+        Viewport {
+            center,
+            width,
+            height,
+            scale: 1,
+            downsample: 1,
+            grid_lines: false,
+        }
+    }
+
+    /// A viewport with the given cell in its top left corner.
+    pub fn from_top_left(top_left: Coord, width: u32, height: u32) -> Viewport {
+        let (left, top) = Viewport::new(width, height).offsets();
+        let center = Coord::new(top_left.x + left as i32, top_left.y - top as i32);
+        Viewport::centered_on(center, width, height)
+    }
+
+    pub fn with_scale(self, scale: u32) -> Viewport {
+        Viewport { scale, ..self }
+    }
+
+    pub fn with_downsample(self, downsample: u32) -> Viewport {
+        Viewport { downsample, ..self }
+    }
+
+    pub fn with_grid_lines(self, grid_lines: bool) -> Viewport {
+        Viewport { grid_lines, ..self }
+    }
+
+    // how many cells sit left of and above the center.
+    fn offsets(&self) -> (i64, i64) {
+        let left = (i64::from(self.width) - 1 - i64::from(self.width / 2)).max(0);
+        let top = i64::from(self.height / 2);
+        (left, top)
+    }
+
+    pub fn top_left(&self) -> Coord {
+        let (left, top) = self.offsets();
+        Coord::new(self.center.x - left as i32, self.center.y + top as i32)
+    }
+
+    /// The corner with the smallest x and y in view.
+    pub fn min(&self) -> Coord {
+        let top_left = self.top_left();
+        Coord::new(top_left.x, top_left.y - self.height as i32 + 1)
+    }
+
+    /// The corner with the largest x and y in view.
+    pub fn max(&self) -> Coord {
+        let top_left = self.top_left();
+        Coord::new(top_left.x + self.width as i32 - 1, top_left.y)
+    }
+
+    /// The coordinate of a cell in the viewport, counting from the top left.
+    pub fn coord_at(&self, col: u32, row: u32) -> Coord {
+        let top_left = self.top_left();
+        Coord::new(top_left.x + col as i32, top_left.y - row as i32)
+    }
+
+    /// The (col, row) of a coordinate, or `None` if it is out of view.
+    pub fn cell_of(&self, c: &Coord) -> Option<(u32, u32)> {
+        let top_left = self.top_left();
+        let col = i64::from(c.x) - i64::from(top_left.x);
+        let row = i64::from(top_left.y) - i64::from(c.y);
+        if col < 0 || row < 0 || col >= i64::from(self.width) || row >= i64::from(self.height) {
+            None
+        } else {
+            Some((col as u32, row as u32))
+        }
+    }
+
+    /// Width in blocks of `downsample` cells, the same as the width in cells when not downsampled.
+    pub fn block_width(&self) -> u32 {
+        self.width.div_ceil(self.downsample.max(1))
+    }
+
+    pub fn block_height(&self) -> u32 {
+        self.height.div_ceil(self.downsample.max(1))
+    }
+
+    pub fn pixel_width(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self
+            .block_width()
+            .checked_mul(self.scale)
+            .ok_or("image too wide")?)
+    }
+
+    pub fn pixel_height(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self
+            .block_height()
+            .checked_mul(self.scale)
+            .ok_or("image too tall")?)
+    }
+
+    /// Whether the pixel at (x, y) is on a grid line: the first pixel across and down
+    /// of every block, and the last pixel of the last ones, which closes the grid
+    /// on the right and at the bottom.
+    pub(crate) fn on_grid_line(&self, x: u32, y: u32) -> bool {
+        let scale = self.scale;
+        let on = |p: u32, blocks: u32| {
+            p.is_multiple_of(scale) || (p % scale == scale - 1 && p / scale + 1 == blocks)
+        };
+        self.grid_lines && scale >= 2 && (on(x, self.block_width()) || on(y, self.block_height()))
+    }
+
+    /// No value in view is larger than this.
+    pub fn max_value(&self) -> u64 {
+        let (min, max) = (self.min(), self.max());
+        let ring = [min.x, min.y, max.x, max.y]
+            .iter()
+            .map(|v| u64::from(v.unsigned_abs()))
+            .max()
+            .unwrap_or(0);
+        (2 * ring + 1).pow(2) - 1
+    }
+
+    // When the view covers most of the spiral up to its largest value,
+    // listing the members of a predicate is quicker than testing every cell.
+    pub(crate) fn lists_members(&self) -> bool {
+        self.max_value() <= 4 * u64::from(self.width) * u64::from(self.height)
+    }
+
+    /// The straight runs of the spiral across `rows`, as (first cell, step, length).
+    /// Values go up by one with each step, so each run is one range of values.
+    #[cfg(feature = "img")]
+    pub(crate) fn arms(&self, rows: std::ops::Range<u32>) -> Vec<(Coord, (i32, i32), u64)> {
+        if rows.is_empty() || self.width == 0 {
+            return Vec::new();
+        }
+        let (top_left, bottom_right) = (
+            self.coord_at(0, rows.start),
+            self.coord_at(self.width - 1, rows.end - 1),
+        );
+        let (x0, x1, y0, y1) = (top_left.x, bottom_right.x, bottom_right.y, top_left.y);
+        let mut arms = Vec::new();
+        let mut push = |start: Coord, step: (i32, i32), len: i32| {
+            if len > 0 {
+                arms.push((start, step, len as u64));
+            }
+        };
+        if x0 <= 0 && 0 <= x1 && y0 <= 0 && 0 <= y1 {
+            push(Coord::new(0, 0), (1, 0), 1);
+        }
+        // rings with an arm along one of the columns
+        let mut rings: Vec<i32> = (x0..=x1).map(|x| x.abs()).filter(|k| *k > 0).collect();
+        rings.sort_unstable();
+        rings.dedup();
+        for &k in &rings {
+            if x0 <= k && k <= x1 {
+                // right arm, upwards from (k, 1 - k)
+                let (lo, hi) = ((1 - k).max(y0), k.min(y1));
+                push(Coord::new(k, lo), (0, 1), hi - lo + 1);
+            }
+            if x0 <= -k && -k <= x1 {
+                // left arm, downwards from (-k, k - 1)
+                let (lo, hi) = ((-k).max(y0), (k - 1).min(y1));
+                push(Coord::new(-k, hi), (0, -1), hi - lo + 1);
+            }
+        }
+        // rings with an arm along one of the rows
+        let mut rings: Vec<i32> = (y0..=y1).map(|y| y.abs()).filter(|k| *k > 0).collect();
+        rings.sort_unstable();
+        rings.dedup();
+        for &k in &rings {
+            if y0 <= k && k <= y1 {
+                // top arm, leftwards from (k - 1, k)
+                let (lo, hi) = ((-k).max(x0), (k - 1).min(x1));
+                push(Coord::new(hi, k), (-1, 0), hi - lo + 1);
+            }
+            if y0 <= -k && -k <= y1 {
+                // bottom arm, rightwards from (1 - k, -k)
+                let (lo, hi) = ((1 - k).max(x0), k.min(x1));
+                push(Coord::new(lo, -k), (1, 0), hi - lo + 1);
+            }
+        }
+        arms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_bounds() {
+        let v = Viewport::new(4, 4);
+        assert_eq!(v.min(), Coord::new(-1, -1));
+        assert_eq!(v.max(), Coord::new(2, 2));
+        let v = Viewport::centered_on(Coord::new(10, -3), 3, 5);
+        assert_eq!(v.min(), Coord::new(9, -5));
+        assert_eq!(v.max(), Coord::new(11, -1));
+        assert_eq!(v.max_value(), 528);
+    }
+    #[test]
+    fn check_cells() {
+        let v = Viewport::centered_on(Coord::new(3, 3), 6, 3);
+        for row in 0..3 {
+            for col in 0..6 {
+                assert_eq!(v.cell_of(&v.coord_at(col, row)), Some((col, row)));
+            }
+        }
+        assert_eq!(v.cell_of(&Coord::new(0, 0)), None);
+    }
+    #[test]
+    fn check_top_left() {
+        for (w, h) in [(1, 1), (4, 4), (5, 3), (6, 7)] {
+            let v = Viewport::centered_on(Coord::new(-7, 2), w, h);
+            assert_eq!(Viewport::from_top_left(v.top_left(), w, h), v);
+        }
+    }
+    #[test]
+    #[cfg(feature = "img")]
+    fn check_arms() {
+        // every cell is on exactly one arm, with the value the arm says
+        for v in [
+            Viewport::new(7, 7),
+            Viewport::from_top_left(Coord::new(2, 7), 8, 9),
+            Viewport::from_top_left(Coord::new(-20, -4), 10, 6),
+        ] {
+            let mut seen = Vec::new();
+            for (start, step, len) in v.arms(1..v.height) {
+                let first = crate::value_of_coord_u64(&start);
+                for along in 0..len as i32 {
+                    let c = Coord::new(start.x + step.0 * along, start.y + step.1 * along);
+                    assert_eq!(crate::value_of_coord_u64(&c), first + along as u64);
+                    seen.push(v.cell_of(&c).unwrap());
+                }
+            }
+            seen.sort_unstable();
+            seen.dedup();
+            assert_eq!(seen.len() as u32, v.width * (v.height - 1));
+            assert!(seen.iter().all(|(_, row)| *row >= 1));
+        }
+    }
+    #[test]
+    fn check_pixels() {
+        let v = Viewport::new(5, 3).with_scale(4);
+        assert_eq!(
+            (v.pixel_width().unwrap(), v.pixel_height().unwrap()),
+            (20, 12)
+        );
+        let v = Viewport::new(10, 9).with_downsample(3);
+        assert_eq!(
+            (v.pixel_width().unwrap(), v.pixel_height().unwrap()),
+            (4, 3)
+        );
+        let v = Viewport::new(70_000, 3).with_scale(70_000);
+        assert!(v.pixel_width().is_err());
+        assert_eq!(v.pixel_height().unwrap(), 210_000);
+    }
+    #[test]
+    fn check_grid_line() {
+        let v = Viewport::new(3, 2).with_scale(4).with_grid_lines(true);
+        let across: Vec<u32> = (0..12).filter(|x| v.on_grid_line(*x, 1)).collect();
+        assert_eq!(across, vec![0, 4, 8, 11]);
+        let down: Vec<u32> = (0..8).filter(|y| v.on_grid_line(1, *y)).collect();
+        assert_eq!(down, vec![0, 4, 7]);
+        assert!(!v.with_grid_lines(false).on_grid_line(0, 0));
+        assert!(!v.with_scale(1).on_grid_line(0, 0));
+    }
+}